@@ -0,0 +1,372 @@
+use std::collections::HashMap;
+
+use rand::RngCore;
+
+use crate::channel::htlc::HtlcLock as HtlcLock;
+use crate::common::{
+    duration::Duration,
+    errortype::ErrorType,
+    nodeid::NodeId,
+    paymentid::PaymentId,
+    paymentresult::PaymentResult,
+    satoshi::Satoshi,
+    timestamp::Timestamp,
+};
+use crate::network::Network as Network;
+use crate::payment::Payment as Payment;
+
+use super::{Attacker as Attacker, JamTarget as JamTarget};
+use super::coalition::Coalition as Coalition;
+
+// What a probe tells about the target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProbeResult {
+    HasRoom,
+    Full,
+    // the probe failed elsewhere on the route
+    Unknown,
+}
+
+// Slot jamming fills the target's slots with many small HTLCs.
+// Liquidity jamming locks the target's whole available liquidity with a few large ones:
+// each jam is sized by the smallest balance available along the route (net of what is in flight).
+#[derive(Debug, Clone)]
+pub enum JamMode {
+    Slot(Satoshi),
+    Liquidity,
+}
+
+#[derive(Debug, Clone)]
+pub struct AdaptiveJammerParams {
+    // probes are small and fail right away, so they cost little
+    pub probe_amount: Satoshi,
+    pub jam_mode: JamMode,
+    pub jam_hold_time: Duration,
+    // the attacker stops once its fees (net of what it earns) exceed this
+    pub budget: Satoshi,
+}
+
+// Instead of following a fixed schedule, this attacker probes its targets for free slots
+// and only sends as many jams as it takes to keep them full.
+// A probe that fails at the target with NoSlots or LowBalance tells the attacker the target is full,
+// a probe that reaches the receiver tells it there is room for another jam.
+#[derive(Debug)]
+pub struct AdaptiveJammer {
+    // the sender and the receiver of the jams, accounted for jointly
+    coalition: Coalition,
+    targets: Vec<JamTarget>,
+    params: AdaptiveJammerParams,
+    // the joint balance of the coalition when the attack started
+    initial_balance: Option<i64>,
+    num_probes: u64,
+    num_jams: u64,
+    // the jams sent so far, watched in the network, and the hops of their targets
+    jams: Vec<(PaymentId, Vec<usize>)>,
+    backed_off: bool,
+}
+
+impl AdaptiveJammer {
+    pub fn new(targets: Vec<JamTarget>, params: AdaptiveJammerParams) -> Self {
+        assert!(!targets.is_empty(), "Attacker must have at least one target");
+        let sender = targets[0].get_sender().clone();
+        let receiver = targets[0].get_receiver().clone();
+        for target in &targets {
+            assert!(target.get_sender() == &sender && target.get_receiver() == &receiver,
+            "All target routes must go from {:?} to {:?}, got {:?}", sender, receiver, target.route);
+        }
+        AdaptiveJammer {
+            coalition: Coalition::new([sender, receiver]),
+            targets,
+            params,
+            initial_balance: None,
+            num_probes: 0,
+            num_jams: 0,
+            jams: Vec::new(),
+            backed_off: false,
+        }
+    }
+
+    pub fn get_num_probes(&self) -> u64 {
+        self.num_probes
+    }
+
+    pub fn get_num_jams(&self) -> u64 {
+        self.num_jams
+    }
+
+    pub fn is_backed_off(&self) -> bool {
+        self.backed_off
+    }
+
+    pub fn set_coalition(&mut self, coalition: Coalition) {
+        // e.g., sybil nodes along the jam routes, whose fees stay with the adversary
+        assert!(self.initial_balance.is_none(), "Coalition must be set before the attack starts");
+        for endpoint in [self.targets[0].get_sender(), self.targets[0].get_receiver()] {
+            assert!(coalition.contains(endpoint), "Coalition must include the attacker's {:?}", endpoint);
+        }
+        self.coalition = coalition;
+    }
+
+    pub fn get_coalition(&self) -> &Coalition {
+        &self.coalition
+    }
+
+    fn get_balance(&self, network: &Network) -> i64 {
+        self.coalition.get_revenue(network).total()
+    }
+
+    pub fn get_cost(&self, network: &Network) -> i64 {
+        // the fees paid since the attack started, net of the fees earned
+        self.initial_balance.map_or(0, |initial_balance| initial_balance - self.get_balance(network))
+    }
+
+    fn is_over_budget(&self, network: &Network) -> bool {
+        self.get_cost(network) > self.params.budget.0 as i64
+    }
+
+    fn probe(&mut self, network: &mut Network, target: &JamTarget, time: &Timestamp, mut rng: &mut dyn RngCore) -> ProbeResult {
+        let payment = network.create_payment(
+            &target.route, self.params.probe_amount, Duration(0), PaymentResult::FAILURE);
+        self.num_probes += 1;
+        match network.attempt_send_payment(&payment, &target.route, time, &mut rng) {
+            Ok(_) => ProbeResult::HasRoom,
+            Err(failure) if target.hops.contains(&failure.erring_hop)
+                && matches!(failure.error_type, ErrorType::NoSlots | ErrorType::LowBalance) => ProbeResult::Full,
+            Err(_) => ProbeResult::Unknown,
+        }
+    }
+
+    fn get_hop_amounts(payment: &Payment) -> Vec<Satoshi> {
+        std::iter::successors(Some(payment), |p| p.get_downstream_payment())
+            .map(|p| p.get_amount())
+            .collect()
+    }
+
+    fn get_liquidity_jam_amount(&self, network: &Network, target: &JamTarget, time: &Timestamp) -> Option<Satoshi> {
+        // The jam must fit into every channel direction along the route.
+        // A circular route passes some of them several times, and locks the amount at each pass.
+        let mut num_passes: HashMap<(&NodeId, &NodeId), u64> = HashMap::new();
+        for nodes in target.route.windows(2) {
+            *num_passes.entry((&nodes[0], &nodes[1])).or_insert(0) += 1;
+        }
+        let available: HashMap<(&NodeId, &NodeId), Satoshi> = num_passes
+            .keys()
+            .filter_map(|&(from, to)| {
+                let ch_in_dir = network.get_channel_in_direction(from, to)?;
+                Some(((from, to), ch_in_dir.get_available_liquidity(time)?))
+            })
+            .collect();
+        let amount = available
+            .iter()
+            .map(|(direction, available)| Satoshi(available.0 / num_passes[direction]))
+            .min()?;
+        // upstream hops also carry the downstream fees: leave room for them
+        let payment = network.create_payment(
+            &target.route, amount, self.params.jam_hold_time.clone(), PaymentResult::FAILURE);
+        let mut locked: HashMap<(&NodeId, &NodeId), Satoshi> = HashMap::new();
+        for (nodes, hop_amount) in target.route.windows(2).zip(Self::get_hop_amounts(&payment)) {
+            let total = locked.entry((&nodes[0], &nodes[1])).or_insert(Satoshi(0));
+            *total = total.add(&hop_amount);
+        }
+        let excess = locked
+            .iter()
+            .filter_map(|(direction, total)| available.get(direction).map(|available| total.0.saturating_sub(available.0)))
+            .max()
+            .unwrap_or(0);
+        Some(Satoshi(amount.0.saturating_sub(excess)))
+    }
+
+    fn get_target_locks(&self, network: &Network) -> Vec<HtlcLock> {
+        // how long the jams have locked (or are expected to lock) their targets
+        self.jams
+            .iter()
+            .flat_map(|(payment_id, hops)| network.get_htlc_locks(payment_id)
+                .into_iter()
+                .filter(|lock| hops.contains(&lock.hop)))
+            .collect()
+    }
+
+    fn jam(&mut self, network: &mut Network, target: &JamTarget, time: &Timestamp, mut rng: &mut dyn RngCore) -> bool {
+        let amount = match &self.params.jam_mode {
+            JamMode::Slot(amount) => *amount,
+            JamMode::Liquidity => match self.get_liquidity_jam_amount(network, target, time) {
+                // what is left is too little to bother
+                Some(amount) if amount.gt(&self.params.probe_amount) => amount,
+                _ => return false,
+            },
+        };
+        let payment = network.create_payment(
+            &target.route, amount, self.params.jam_hold_time.clone(), PaymentResult::FAILURE);
+        let result = network.attempt_send_payment(&payment, &target.route, time, &mut rng);
+        if let Ok(payment_id) = &result {
+            network.watch_payment(payment_id);
+            self.jams.push((payment_id.clone(), target.hops.clone()));
+            self.num_jams += 1;
+        }
+        result.is_ok()
+    }
+}
+
+impl Attacker for AdaptiveJammer {
+    fn get_liquidity_locked(&self, network: &Network) -> u64 {
+        // as long as the jams have actually been held at the targets
+        self.get_target_locks(network).iter().map(|lock| lock.get_amount_time()).sum()
+    }
+
+    fn get_jam_intervals(&self, network: &Network, from: &NodeId, to: &NodeId) -> Vec<(Timestamp, Timestamp)> {
+        self.get_target_locks(network)
+            .into_iter()
+            .filter(|lock| &lock.from == from && &lock.to == to)
+            .map(|lock| (lock.added_at, lock.resolved_at))
+            .collect()
+    }
+
+    fn get_active_interval(&self, network: &Network) -> Option<(Timestamp, Timestamp)> {
+        let locks = self.get_target_locks(network);
+        let start = locks.iter().map(|lock| lock.added_at.clone()).min()?;
+        let end = locks.iter().map(|lock| lock.resolved_at.clone()).max()?;
+        Some((start, end))
+    }
+
+    fn act(&mut self, network: &mut Network, time: &Timestamp, rng: &mut dyn RngCore) {
+        if self.initial_balance.is_none() {
+            self.initial_balance = Some(self.get_balance(network));
+        }
+        let targets = self.targets.clone();
+        for target in &targets {
+            // keep jamming until a probe finds the target full
+            loop {
+                if self.is_over_budget(network) {
+                    self.backed_off = true;
+                    return;
+                }
+                let probe_result = self.probe(network, target, time, rng);
+                if probe_result != ProbeResult::HasRoom || !self.jam(network, target, time, rng) {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel::channelindirection::ChannelInDirection;
+    use crate::testutils::{self, flat_fee, node};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn add_channel(network: &mut Network, from: &str, to: &str, num_slots: usize) {
+        testutils::add_channel(network, from, to, ChannelInDirection::new(Some(num_slots), flat_fee(1), None, None, None));
+    }
+
+    fn add_channel_with_balance(network: &mut Network, from: &str, to: &str, balance: Satoshi) {
+        let ch_in_dir = ChannelInDirection::new(Some(10), flat_fee(1), None, None, None);
+        testutils::add_channel_with_balance(network, from, to, balance, ch_in_dir);
+    }
+
+    fn example_network() -> Network {
+        // Mallory jams Bob - Charlie (3 slots) via payments to her own node Eve
+        let mut network = Network::new(None);
+        add_channel(&mut network, "Mallory", "Bob", 10);
+        add_channel(&mut network, "Bob", "Charlie", 3);
+        add_channel(&mut network, "Charlie", "Eve", 10);
+        network
+    }
+
+    fn attacker(budget: Satoshi) -> AdaptiveJammer {
+        let route = vec![node("Mallory"), node("Bob"), node("Charlie"), node("Eve")];
+        AdaptiveJammer::new(
+            vec![JamTarget::new(route, 1)],
+            AdaptiveJammerParams {
+                probe_amount: Satoshi(1),
+                jam_mode: JamMode::Slot(Satoshi(1000)),
+                jam_hold_time: Duration(10),
+                budget,
+            },
+        )
+    }
+
+    #[test]
+    fn adaptive_jammer_fills_target() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = example_network();
+        let mut attacker = attacker(Satoshi(1000));
+        attacker.act(&mut network, &Timestamp(0), &mut rng);
+        // three jams fill the target, the fourth probe finds it full
+        assert_eq!(attacker.get_num_jams(), 3);
+        assert_eq!(attacker.get_num_probes(), 4);
+        let target = network.get_channel_in_direction(&node("Bob"), &node("Charlie")).unwrap();
+        assert_eq!(target.get_num_unendorsed_htlcs_in_flight(&Timestamp(0)), 3);
+        // while the jams are in flight, one probe is enough
+        attacker.act(&mut network, &Timestamp(5), &mut rng);
+        assert_eq!(attacker.get_num_jams(), 3);
+        assert_eq!(attacker.get_num_probes(), 5);
+        // once they have resolved, the target is jammed again
+        attacker.act(&mut network, &Timestamp(10), &mut rng);
+        assert_eq!(attacker.get_num_jams(), 6);
+        assert!(!attacker.is_backed_off());
+        assert!(attacker.get_cost(&network) > 0);
+        // the jams lock the target from 0 to 20, one after the other
+        assert_eq!(attacker.get_time_jammed(&network, &node("Bob"), &node("Charlie")), Duration(20));
+        assert_eq!(attacker.get_time_jammed(&network, &node("Mallory"), &node("Bob")), Duration(0));
+    }
+
+    #[test]
+    fn adaptive_jammer_circular_route() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = example_network();
+        add_channel(&mut network, "Charlie", "Mallory", 10);
+        // each jam passes Bob - Charlie three times
+        let target = JamTarget::circular(vec![node("Mallory"), node("Bob"), node("Charlie")], 1, 3);
+        let mut attacker = AdaptiveJammer::new(vec![target], AdaptiveJammerParams {
+            probe_amount: Satoshi(1),
+            jam_mode: JamMode::Slot(Satoshi(1000)),
+            jam_hold_time: Duration(10),
+            budget: Satoshi(1000),
+        });
+        attacker.act(&mut network, &Timestamp(0), &mut rng);
+        assert_eq!(attacker.get_num_jams(), 1);
+        let target = network.get_channel_in_direction(&node("Bob"), &node("Charlie")).unwrap();
+        assert_eq!(target.get_num_unendorsed_htlcs_in_flight(&Timestamp(0)), 3);
+    }
+
+    #[test]
+    fn adaptive_jammer_liquidity_mode() {
+        let mut rng = StdRng::seed_from_u64(0);
+        // the channels are equally large, but Bob has the least balance on his side of the target
+        let mut network = Network::new(None);
+        add_channel_with_balance(&mut network, "Mallory", "Bob", Satoshi(800_000));
+        add_channel_with_balance(&mut network, "Bob", "Charlie", Satoshi(300_000));
+        add_channel_with_balance(&mut network, "Charlie", "Eve", Satoshi(800_000));
+        let route = vec![node("Mallory"), node("Bob"), node("Charlie"), node("Eve")];
+        let mut attacker = AdaptiveJammer::new(vec![JamTarget::new(route, 1)], AdaptiveJammerParams {
+            probe_amount: Satoshi(1),
+            jam_mode: JamMode::Liquidity,
+            jam_hold_time: Duration(10),
+            budget: Satoshi(1000),
+        });
+        attacker.act(&mut network, &Timestamp(0), &mut rng);
+        // a single jam locks all of the target's balance (including Charlie's fee)
+        assert_eq!(attacker.get_num_jams(), 1);
+        assert_eq!(attacker.get_num_probes(), 2);
+        let target = network.get_channel_in_direction(&node("Bob"), &node("Charlie")).unwrap();
+        assert_eq!(target.get_num_unendorsed_htlcs_in_flight(&Timestamp(0)), 1);
+        assert_eq!(target.get_available_liquidity(&Timestamp(0)), Some(Satoshi(0)));
+        assert_eq!(attacker.get_liquidity_locked(&network), 300_000 * 10);
+        // the jam fails, so the balances stay as they were
+        network.resolve_all_htlcs();
+        assert_eq!(network.get_balance(&testutils::cid("Bob", "Charlie"), &node("Bob"), &node("Charlie")), Satoshi(300_000));
+        assert_eq!(attacker.get_liquidity_locked(&network), 300_000 * 10);
+    }
+
+    #[test]
+    fn adaptive_jammer_backs_off_over_budget() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = example_network();
+        let mut attacker = attacker(Satoshi(0));
+        attacker.act(&mut network, &Timestamp(0), &mut rng);
+        assert_eq!(attacker.get_num_jams(), 1);
+        assert!(attacker.is_backed_off());
+    }
+}
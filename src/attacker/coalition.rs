@@ -0,0 +1,153 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::common::{fee::FeeType, nodeid::NodeId, revenue::Revenue, satoshi::Satoshi};
+use crate::network::Network as Network;
+
+// A set of nodes controlled by one adversary, e.g., the sender and the receiver of jams,
+// or several sybil nodes along the route.
+// Fees paid from one member to another stay within the coalition,
+// so only the joint figures tell whether an attack costs the adversary anything.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coalition {
+    members: BTreeSet<NodeId>,
+}
+
+// The joint profit and loss of a coalition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoalitionReport {
+    // net revenue by fee type, summed over the members: transfers between members cancel out
+    pub revenue: Revenue,
+    // fees paid to and earned from nodes outside the coalition
+    pub fees_paid: Satoshi,
+    pub fees_earned: Satoshi,
+    pub fees_paid_by_type: HashMap<FeeType, Satoshi>,
+    // amount times time locked in the members' outgoing HTLCs, including those still in flight
+    pub capital_locked: u64,
+}
+
+impl CoalitionReport {
+    pub fn get_profit(&self) -> i64 {
+        // negative if the attack costs the coalition
+        self.revenue.total()
+    }
+
+    pub fn get_fees_paid(&self, fee_type: FeeType) -> Satoshi {
+        self.fees_paid_by_type.get(&fee_type).copied().unwrap_or(Satoshi(0))
+    }
+}
+
+impl Coalition {
+    pub fn new(members: impl IntoIterator<Item = NodeId>) -> Self {
+        let members: BTreeSet<NodeId> = members.into_iter().collect();
+        assert!(!members.is_empty(), "Coalition must have at least one member");
+        Coalition { members }
+    }
+
+    pub fn get_members(&self) -> &BTreeSet<NodeId> {
+        &self.members
+    }
+
+    pub fn contains(&self, node: &NodeId) -> bool {
+        self.members.contains(node)
+    }
+
+    pub fn get_revenue(&self, network: &Network) -> Revenue {
+        self.members
+            .iter()
+            .fold(Revenue::default(), |total, member| total.add(network.get_revenue(member)))
+    }
+
+    pub fn get_report(&self, network: &Network) -> CoalitionReport {
+        let mut report = CoalitionReport {
+            revenue: self.get_revenue(network),
+            fees_paid: Satoshi(0),
+            fees_earned: Satoshi(0),
+            fees_paid_by_type: HashMap::new(),
+            capital_locked: 0,
+        };
+        for (payer, payee, fee_type, amount) in network.get_fee_flows() {
+            match (self.contains(payer), self.contains(payee)) {
+                (true, false) => {
+                    report.fees_paid = report.fees_paid.add(amount);
+                    let fees_paid = report.fees_paid_by_type.entry(*fee_type).or_insert(Satoshi(0));
+                    *fees_paid = fees_paid.add(amount);
+                }
+                (false, true) => report.fees_earned = report.fees_earned.add(amount),
+                _ => {}
+            }
+        }
+        report.capital_locked = self.members
+            .iter()
+            .filter_map(|member| network.get_node(member))
+            .map(|node| node.get_capital_locked() + network.get_capital_in_flight(node.get_id()))
+            .sum();
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel::channelindirection::ChannelInDirection;
+    use crate::common::{
+        duration::Duration,
+        hopdelay::HopDelay,
+        paymentresult::PaymentResult,
+        timestamp::Timestamp,
+    };
+    use crate::testutils::{self, flat_fee, node};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn add_channel(network: &mut Network, from: &str, to: &str) {
+        testutils::add_channel(network, from, to, ChannelInDirection::new(None, flat_fee(1), flat_fee(2), None, None));
+    }
+
+    #[test]
+    fn coalition_report() {
+        // Mallory sends to her own node Eve via Bob and Charlie
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = Network::new(None);
+        add_channel(&mut network, "Mallory", "Bob");
+        add_channel(&mut network, "Bob", "Charlie");
+        add_channel(&mut network, "Charlie", "Eve");
+        let route = vec![node("Mallory"), node("Bob"), node("Charlie"), node("Eve")];
+        let payment = network.create_payment(&route, Satoshi(100), Duration(10), PaymentResult::SUCCESS);
+        assert!(network.attempt_send_payment(&payment, &route, &Timestamp(0), &mut rng).is_ok());
+        let coalition = Coalition::new([node("Mallory"), node("Eve")]);
+        // the capital locked in flight counts before the HTLCs are resolved
+        assert_eq!(coalition.get_report(&network).capital_locked, 106 * 10);
+        network.resolve_all_htlcs();
+        let report = coalition.get_report(&network);
+        // Mallory pays 3 upfront and 4 on success, Eve earns back 1 upfront (the receiver charges no success fee)
+        assert_eq!(report.fees_paid, Satoshi(7));
+        assert_eq!(report.get_fees_paid(FeeType::Upfront), Satoshi(3));
+        assert_eq!(report.get_fees_paid(FeeType::Success), Satoshi(4));
+        assert_eq!(report.get_fees_paid(FeeType::Hold), Satoshi(0));
+        assert_eq!(report.fees_earned, Satoshi(1));
+        assert_eq!(report.get_profit(), 1 - 7);
+        assert_eq!(report.revenue.upfront, -2);
+        // Mallory locks the amount plus downstream fees for the whole hold time
+        assert_eq!(report.capital_locked, 106 * 10);
+        // a lone sender looks like it pays more
+        let sender_only = Coalition::new([node("Mallory")]).get_report(&network);
+        assert_eq!(sender_only.get_profit(), -7);
+    }
+
+    #[test]
+    fn coalition_capital_locked_by_failed_payments() {
+        // each hop takes 3 time units: Mallory's payment reaches Charlie - Eve and fails there at 6
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = Network::new(None);
+        add_channel(&mut network, "Mallory", "Bob");
+        add_channel(&mut network, "Bob", "Charlie");
+        let ch_in_dir = ChannelInDirection::new(None, flat_fee(1), flat_fee(2), Some(1.0), None);
+        testutils::add_channel(&mut network, "Charlie", "Eve", ch_in_dir);
+        network.set_hop_delay(HopDelay::new(Duration(3), Duration(3)));
+        let route = vec![node("Mallory"), node("Bob"), node("Charlie"), node("Eve")];
+        let payment = network.create_payment(&route, Satoshi(100), Duration(10), PaymentResult::SUCCESS);
+        assert!(network.attempt_send_payment(&payment, &route, &Timestamp(0), &mut rng).is_err());
+        // the failure takes another 3 time units per hop back: Mallory's HTLC is locked from 0 until 12
+        let report = Coalition::new([node("Mallory"), node("Eve")]).get_report(&network);
+        assert_eq!(report.capital_locked, 106 * 12);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::common::nodeid::NodeId;
+use crate::network::Network as Network;
+use crate::simulator::Simulator as Simulator;
+
+use super::JamTarget as JamTarget;
+use super::coalition::Coalition as Coalition;
+use super::isolation::{get_isolation_targets, IsolationDirection};
+use super::report::{AttackCostParams, AttackReport};
+
+pub fn get_competition_targets(
+    network: &Network,
+    victim: &NodeId,
+    rival: &NodeId,
+    sender: &NodeId,
+    receiver: &NodeId,
+) -> Vec<JamTarget> {
+    // Jamming the victim only pays off where honest senders can fall back to the rival:
+    // jam the victim's outgoing channel directions to the nodes the rival also forwards to.
+    get_isolation_targets(network, victim, IsolationDirection::Outgoing, sender, receiver)
+        .into_iter()
+        .filter(|target| target
+            .get_target_directions()
+            .iter()
+            .all(|(_, to)| network.get_channel_in_direction(rival, to).is_some()))
+        .collect()
+}
+
+// The attacker runs a routing node (the rival) in parallel to the victim,
+// and jams the victim to divert honest traffic to the rival.
+// The rival is not part of the jamming coalition:
+// its revenue is what the attack is for, not a discount on the jamming.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompetitionReport {
+    pub rival: NodeId,
+    pub rival_revenue: i64,
+    pub rival_revenue_baseline: i64,
+    pub attack: AttackReport,
+}
+
+impl CompetitionReport {
+    pub fn new(
+        rival: &NodeId,
+        victim: &NodeId,
+        coalition: &Coalition,
+        cost_params: &AttackCostParams,
+        attacked: &Simulator,
+        baseline: &Simulator,
+    ) -> Self {
+        assert!(!coalition.contains(rival), "Rival {:?} must not be in the jamming coalition", rival);
+        CompetitionReport {
+            rival: rival.clone(),
+            rival_revenue: attacked.get_network().get_revenue(rival).total(),
+            rival_revenue_baseline: baseline.get_network().get_revenue(rival).total(),
+            attack: AttackReport::new(coalition, cost_params, attacked, baseline, std::slice::from_ref(victim)),
+        }
+    }
+
+    pub fn get_extra_revenue(&self) -> i64 {
+        self.rival_revenue - self.rival_revenue_baseline
+    }
+
+    pub fn get_profit(&self) -> i64 {
+        // what diverting the traffic earns, net of what jamming the victim costs
+        self.get_extra_revenue() - self.attack.get_total_cost()
+    }
+
+    pub fn is_profitable(&self) -> bool {
+        self.get_profit() > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attacker::adaptive::{AdaptiveJammer, AdaptiveJammerParams, JamMode};
+    use crate::channel::channelindirection::ChannelInDirection;
+    use crate::common::{
+        duration::Duration,
+        paymentresult::PaymentResult,
+        satoshi::Satoshi,
+        timestamp::Timestamp,
+    };
+    use crate::schedule::event::Event;
+    use crate::schedule::schedule::Schedule;
+    use crate::sender::SenderParams;
+    use crate::testutils::{self, flat_fee, node};
+
+    fn add_channel(network: &mut Network, from: &str, to: &str, num_slots: usize) {
+        testutils::add_channel(network, from, to, ChannelInDirection::new(Some(num_slots), flat_fee(10), flat_fee(1), None, None));
+    }
+
+    fn simulator() -> Simulator {
+        // Alice pays Dave via Bob (the victim) or Rachel (Mallory's rival node).
+        // Mallory jams Bob - Dave with payments to her node Eve.
+        let mut network = Network::new(None);
+        add_channel(&mut network, "Alice", "Bob", 10);
+        add_channel(&mut network, "Alice", "Rachel", 10);
+        add_channel(&mut network, "Bob", "Dave", 2);
+        add_channel(&mut network, "Rachel", "Dave", 10);
+        add_channel(&mut network, "Mallory", "Bob", 10);
+        add_channel(&mut network, "Dave", "Eve", 10);
+        let mut schedule = Schedule::new(Timestamp(100));
+        for time in [0, 50] {
+            let event = Event::new(
+                node("Alice"), node("Dave"), Satoshi(100 + time), Duration(5), PaymentResult::SUCCESS, None);
+            schedule.put_event(Timestamp(time), event, None);
+        }
+        // honest senders retry around the channels that failed
+        let sender_params = SenderParams { max_num_attempts: 2, max_retry_time: Duration(5), ..SenderParams::default() };
+        Simulator::new(network, schedule, Some(sender_params), 0)
+    }
+
+    #[test]
+    fn competition_targets() {
+        let simulator = simulator();
+        let targets = get_competition_targets(
+            simulator.get_network(), &node("Bob"), &node("Rachel"), &node("Mallory"), &node("Eve"));
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].route, vec![node("Mallory"), node("Bob"), node("Dave"), node("Eve")]);
+        // Rachel can't take over traffic to Bob's other neighbors
+        let targets = get_competition_targets(
+            simulator.get_network(), &node("Bob"), &node("Alice"), &node("Mallory"), &node("Eve"));
+        assert!(targets.is_empty());
+    }
+
+    #[test]
+    fn competition_report() {
+        let mut baseline = simulator();
+        baseline.run();
+        let mut attacked = simulator();
+        let targets = get_competition_targets(
+            attacked.get_network(), &node("Bob"), &node("Rachel"), &node("Mallory"), &node("Eve"));
+        let attacker = AdaptiveJammer::new(targets, AdaptiveJammerParams {
+            probe_amount: Satoshi(1),
+            jam_mode: JamMode::Slot(Satoshi(1000)),
+            jam_hold_time: Duration(100),
+            budget: Satoshi(1000),
+        });
+        let coalition = attacker.get_coalition().clone();
+        attacked.add_attacker(Box::new(attacker));
+        attacked.run();
+        let cost_params = AttackCostParams { on_chain_cost: Satoshi(0), capital_cost_rate: 0.0 };
+        let report = CompetitionReport::new(
+            &node("Rachel"), &node("Bob"), &coalition, &cost_params, &attacked, &baseline);
+        // without the attack, both payments go through Bob
+        assert_eq!(report.rival_revenue_baseline, 0);
+        assert_eq!(report.attack.num_honest_failed, 0);
+        // with the attack, both fall back to Rachel, each paying her a success and an upfront fee
+        assert_eq!(report.get_extra_revenue(), 2 * (10 + 1));
+        // but the jams' upfront fees cost Mallory far more than that
+        assert_eq!(report.attack.get_total_cost(), 200);
+        assert_eq!(report.get_profit(), 22 - 200);
+        assert!(!report.is_profitable());
+    }
+}
@@ -0,0 +1,256 @@
+use crate::common::{nodeid::NodeId, satoshi::Satoshi};
+use crate::network::Network as Network;
+use crate::router;
+use crate::simulator::Simulator as Simulator;
+
+use super::{get_time_covered, JamTarget as JamTarget};
+use super::coalition::Coalition as Coalition;
+use super::report::{AttackCostParams, AttackReport};
+
+// Which of the victim's channel directions to jam:
+// incoming ones cut the victim off from payments to it (e.g., a merchant's),
+// outgoing ones from the payments it sends or forwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IsolationDirection {
+    Incoming,
+    Outgoing,
+}
+
+// The victim's channel directions in the given direction, as (from, to) pairs.
+pub fn get_victim_directions(
+    network: &Network,
+    victim: &NodeId,
+    direction: IsolationDirection,
+) -> Vec<(NodeId, NodeId)> {
+    network
+        .get_neighbors(victim)
+        .map(|neighbor| match direction {
+            IsolationDirection::Incoming => (neighbor.clone(), victim.clone()),
+            IsolationDirection::Outgoing => (victim.clone(), neighbor.clone()),
+        })
+        .filter(|(from, to)| network.get_channel_in_direction(from, to).is_some())
+        .collect()
+}
+
+pub fn get_isolation_targets(
+    network: &Network,
+    victim: &NodeId,
+    direction: IsolationDirection,
+    sender: &NodeId,
+    receiver: &NodeId,
+) -> Vec<JamTarget> {
+    // One target per channel direction of the victim, with a route via the neighbor at its other end.
+    // The attacker's own channels with the victim are left alone,
+    // and so are the directions it can't route through.
+    get_victim_directions(network, victim, direction)
+        .into_iter()
+        .filter(|(from, to)| ![sender, receiver].iter().any(|endpoint| *endpoint == from || *endpoint == to))
+        .filter_map(|(from, to)| {
+            let route = router::find_route(network, sender, receiver, &[from.clone(), to.clone()], &[], &[])?.nodes;
+            // the route may pass the target more than once
+            let hops = route
+                .windows(2)
+                .enumerate()
+                .filter(|(_, nodes)| nodes[0] == from && nodes[1] == to)
+                .map(|(hop, _)| hop)
+                .collect();
+            Some(JamTarget { route, hops })
+        })
+        .collect()
+}
+
+// How much of the victim's routing capacity and honest traffic an isolation attack denied,
+// counting only the victim's channels with nodes outside the attacker's coalition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IsolationReport {
+    pub victim: NodeId,
+    pub capacity: Satoshi,
+    // the capacity of each channel direction times the time the attackers kept it jammed
+    pub capacity_time_jammed: u64,
+    // honest payment parts that made it through the victim's channel directions
+    pub num_parts_through: u64,
+    pub num_parts_through_baseline: u64,
+    pub attack: AttackReport,
+}
+
+impl IsolationReport {
+    pub fn new(
+        victim: &NodeId,
+        direction: IsolationDirection,
+        coalition: &Coalition,
+        cost_params: &AttackCostParams,
+        attacked: &Simulator,
+        baseline: &Simulator,
+    ) -> Self {
+        let network = attacked.get_network();
+        let victim_directions: Vec<(NodeId, NodeId)> = get_victim_directions(network, victim, direction)
+            .into_iter()
+            .filter(|(from, to)| !coalition.contains(from) && !coalition.contains(to))
+            .collect();
+        let get_capacity = |from: &NodeId, to: &NodeId| network
+            .get_cid_in_direction(from, to)
+            .and_then(|cid| network.get_channel(cid))
+            .map_or(0, |ch| ch.get_capacity().0);
+        // the attackers' jams may overlap, or take turns
+        let get_time_jammed = |from: &NodeId, to: &NodeId| get_time_covered(attacked
+            .get_attackers()
+            .iter()
+            .flat_map(|attacker| attacker.get_jam_intervals(network, from, to))
+            .collect());
+        let num_parts_through = |simulator: &Simulator| victim_directions
+            .iter()
+            .map(|(from, to)| simulator.get_num_parts_through(from, to))
+            .sum();
+        IsolationReport {
+            victim: victim.clone(),
+            capacity: Satoshi(victim_directions.iter().map(|(from, to)| get_capacity(from, to)).sum()),
+            capacity_time_jammed: victim_directions
+                .iter()
+                .map(|(from, to)| get_capacity(from, to) * get_time_jammed(from, to).0)
+                .sum(),
+            num_parts_through: num_parts_through(attacked),
+            num_parts_through_baseline: num_parts_through(baseline),
+            attack: AttackReport::new(coalition, cost_params, attacked, baseline, std::slice::from_ref(victim)),
+        }
+    }
+
+    pub fn get_capacity_denied_share(&self) -> f64 {
+        // averaged over the time the attack is active
+        let total = self.capacity.0 as f64 * self.attack.duration.0 as f64;
+        if total > 0.0 { self.capacity_time_jammed as f64 / total } else { 0.0 }
+    }
+
+    pub fn get_traffic_denied_share(&self) -> Option<f64> {
+        // the share of the honest traffic through the victim in the baseline that didn't make it
+        (self.num_parts_through_baseline > 0).then(|| {
+            let num_denied = self.num_parts_through_baseline.saturating_sub(self.num_parts_through);
+            num_denied as f64 / self.num_parts_through_baseline as f64
+        })
+    }
+
+    pub fn get_total_cost(&self) -> i64 {
+        self.attack.get_total_cost()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::RngCore;
+    use crate::attacker::Attacker;
+    use crate::attacker::adaptive::{AdaptiveJammer, AdaptiveJammerParams, JamMode};
+    use crate::channel::channelindirection::ChannelInDirection;
+    use crate::common::{duration::Duration, paymentresult::PaymentResult, timestamp::Timestamp};
+    use crate::schedule::event::Event;
+    use crate::schedule::schedule::Schedule;
+    use crate::testutils::{self, flat_fee, node};
+
+    fn add_channel(network: &mut Network, node_a: &str, node_b: &str, num_slots: usize) {
+        testutils::add_bidirectional_channel(
+            network, node_a, node_b, || ChannelInDirection::new(Some(num_slots), flat_fee(1), None, None, None));
+    }
+
+    fn simulator() -> Simulator {
+        // Alice and Bob pay the merchant Victor, who also has a channel with Mallory's node Eve
+        let mut network = Network::new(None);
+        add_channel(&mut network, "Alice", "Victor", 2);
+        add_channel(&mut network, "Bob", "Victor", 2);
+        add_channel(&mut network, "Victor", "Eve", 10);
+        add_channel(&mut network, "Mallory", "Alice", 10);
+        add_channel(&mut network, "Mallory", "Bob", 10);
+        let mut schedule = Schedule::new(Timestamp(100));
+        for (sender, time) in [("Alice", 0), ("Bob", 0), ("Alice", 50), ("Bob", 50)] {
+            let event = Event::new(
+                node(sender), node("Victor"), Satoshi(100 + time), Duration(5), PaymentResult::SUCCESS, None);
+            schedule.put_event(Timestamp(time), event, None);
+        }
+        Simulator::new(network, schedule, None, 0)
+    }
+
+    #[test]
+    fn isolation_targets() {
+        let simulator = simulator();
+        let network = simulator.get_network();
+        let targets = get_isolation_targets(
+            network, &node("Victor"), IsolationDirection::Incoming, &node("Mallory"), &node("Eve"));
+        // Eve's channel with Victor is the attacker's own
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].route, vec![node("Mallory"), node("Alice"), node("Victor"), node("Eve")]);
+        assert_eq!(targets[0].get_target_directions(), vec![(node("Alice"), node("Victor"))]);
+        assert_eq!(targets[1].get_target_directions(), vec![(node("Bob"), node("Victor"))]);
+        let targets = get_isolation_targets(
+            network, &node("Victor"), IsolationDirection::Outgoing, &node("Mallory"), &node("Eve"));
+        assert_eq!(targets[0].route, vec![
+            node("Mallory"), node("Alice"), node("Victor"), node("Alice"), node("Victor"), node("Eve"),
+        ]);
+        assert_eq!(targets[0].hops, vec![2]);
+    }
+
+    #[test]
+    fn isolation_report() {
+        let mut baseline = simulator();
+        baseline.run();
+        let mut attacked = simulator();
+        let targets = get_isolation_targets(
+            attacked.get_network(), &node("Victor"), IsolationDirection::Incoming, &node("Mallory"), &node("Eve"));
+        let attacker = AdaptiveJammer::new(targets, AdaptiveJammerParams {
+            probe_amount: Satoshi(1),
+            jam_mode: JamMode::Slot(Satoshi(1000)),
+            jam_hold_time: Duration(100),
+            budget: Satoshi(1000),
+        });
+        let coalition = attacker.get_coalition().clone();
+        attacked.add_attacker(Box::new(attacker));
+        attacked.run();
+        let cost_params = AttackCostParams { on_chain_cost: Satoshi(50), capital_cost_rate: 0.0 };
+        let report = IsolationReport::new(
+            &node("Victor"), IsolationDirection::Incoming, &coalition, &cost_params, &attacked, &baseline);
+        // Victor receives nothing while both of his honest channels are jammed
+        assert_eq!(report.capacity, Satoshi(2_000_000));
+        assert_eq!(report.num_parts_through_baseline, 4);
+        assert_eq!(report.num_parts_through, 0);
+        assert_eq!(report.get_traffic_denied_share(), Some(1.0));
+        // the jams hold both channels for the whole time the attack is active
+        assert_eq!(report.get_capacity_denied_share(), 1.0);
+        assert!(report.get_total_cost() > 50);
+    }
+
+    // an attacker that has locked a channel direction during the given interval
+    #[derive(Debug)]
+    struct FixedJam {
+        from: NodeId,
+        to: NodeId,
+        interval: (Timestamp, Timestamp),
+    }
+
+    impl Attacker for FixedJam {
+        fn act(&mut self, _network: &mut Network, _time: &Timestamp, _rng: &mut dyn RngCore) {}
+
+        fn get_jam_intervals(&self, _network: &Network, from: &NodeId, to: &NodeId) -> Vec<(Timestamp, Timestamp)> {
+            if from == &self.from && to == &self.to { vec![self.interval.clone()] } else { Vec::new() }
+        }
+
+        fn get_active_interval(&self, _network: &Network) -> Option<(Timestamp, Timestamp)> {
+            Some(self.interval.clone())
+        }
+    }
+
+    #[test]
+    fn isolation_report_attackers_take_turns() {
+        // two attackers jam Alice - Victor one after the other, and one of them Bob - Victor as well
+        let baseline = simulator();
+        let mut attacked = simulator();
+        for (from, start, end) in [("Alice", 0, 10), ("Alice", 20, 30), ("Bob", 20, 30)] {
+            attacked.add_attacker(Box::new(FixedJam {
+                from: node(from),
+                to: node("Victor"),
+                interval: (Timestamp(start), Timestamp(end)),
+            }));
+        }
+        let cost_params = AttackCostParams { on_chain_cost: Satoshi(0), capital_cost_rate: 0.0 };
+        let coalition = Coalition::new([node("Mallory")]);
+        let report = IsolationReport::new(
+            &node("Victor"), IsolationDirection::Incoming, &coalition, &cost_params, &attacked, &baseline);
+        assert_eq!(report.capacity_time_jammed, 1_000_000 * (10 + 10) + 1_000_000 * 10);
+    }
+}
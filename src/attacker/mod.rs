@@ -0,0 +1,118 @@
+pub mod adaptive;
+pub mod coalition;
+pub mod competition;
+pub mod isolation;
+pub mod report;
+
+use std::fmt::Debug;
+
+use rand::RngCore;
+
+use crate::common::{duration::Duration, nodeid::NodeId, timestamp::Timestamp};
+use crate::network::Network as Network;
+
+// An attack strategy that acts on the network while the simulation runs,
+// alongside the scheduled honest payments.
+// It is given the chance to act at the time of every scheduled event.
+pub trait Attacker: Debug {
+    fn act(&mut self, network: &mut Network, time: &Timestamp, rng: &mut dyn RngCore);
+
+    // the amount times the time the attack has locked in its targets
+    fn get_liquidity_locked(&self, _network: &Network) -> u64 {
+        0
+    }
+
+    // when the attack has locked the channel direction from one node to another: (start, end) for each lock
+    fn get_jam_intervals(&self, _network: &Network, _from: &NodeId, _to: &NodeId) -> Vec<(Timestamp, Timestamp)> {
+        Vec::new()
+    }
+
+    // how long the attack has kept the channel direction from one node to another jammed
+    fn get_time_jammed(&self, network: &Network, from: &NodeId, to: &NodeId) -> Duration {
+        get_time_covered(self.get_jam_intervals(network, from, to))
+    }
+
+    // from when the attack first locked one of its targets until its last lock there ends (or is expected to)
+    fn get_active_interval(&self, _network: &Network) -> Option<(Timestamp, Timestamp)> {
+        None
+    }
+}
+
+// The time covered by at least one of the intervals: overlapping jams count once.
+pub fn get_time_covered(mut intervals: Vec<(Timestamp, Timestamp)>) -> Duration {
+    intervals.sort();
+    let mut time_covered = 0;
+    let mut covered_until = Timestamp(0);
+    for (start, end) in intervals {
+        time_covered += end.0.saturating_sub(start.max(covered_until.clone()).0);
+        covered_until = covered_until.max(end);
+    }
+    Duration(time_covered)
+}
+
+// A channel direction to jam, and the attacker's route through it:
+// the route goes from the attacker's sender to its (colluding) receiver.
+// A circular route starts and ends at the attacker and passes the target several times,
+// so that each payment holds several of the target's slots.
+#[derive(Debug, Clone)]
+pub struct JamTarget {
+    pub route: Vec<NodeId>,
+    // the target is the channel direction from route[hop] to route[hop + 1] for each of these hops
+    pub hops: Vec<usize>,
+}
+
+impl JamTarget {
+    pub fn new(route: Vec<NodeId>, hop: usize) -> Self {
+        assert!(hop + 1 < route.len(), "Target hop {} is not on the route {:?}", hop, route);
+        JamTarget { route, hops: vec![hop] }
+    }
+
+    pub fn circular(cycle: Vec<NodeId>, hop: usize, num_loops: usize) -> Self {
+        // The cycle starts at the attacker, e.g., [Mallory, Bob, Charlie] for a route
+        // Mallory - Bob - Charlie - Mallory - Bob - Charlie - Mallory with two loops.
+        // The target is the channel direction from cycle[hop] to the next node of the cycle.
+        assert!(cycle.len() >= 3, "A cycle needs at least three nodes, got {:?}", cycle);
+        assert!(hop < cycle.len(), "Target hop {} is not on the cycle {:?}", hop, cycle);
+        assert!(num_loops > 0, "A circular route needs at least one loop");
+        let mut route: Vec<NodeId> = cycle.iter().cycle().take(cycle.len() * num_loops).cloned().collect();
+        route.push(cycle[0].clone());
+        let hops = (0..num_loops).map(|i| i * cycle.len() + hop).collect();
+        JamTarget { route, hops }
+    }
+
+    pub fn get_sender(&self) -> &NodeId {
+        self.route.first().unwrap()
+    }
+
+    pub fn get_receiver(&self) -> &NodeId {
+        self.route.last().unwrap()
+    }
+
+    pub fn get_num_slots_per_payment(&self) -> usize {
+        self.hops.len()
+    }
+
+    pub fn get_target_directions(&self) -> Vec<(NodeId, NodeId)> {
+        self.hops.iter().map(|hop| (self.route[*hop].clone(), self.route[*hop + 1].clone())).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::node;
+
+    #[test]
+    fn jamtarget_circular() {
+        let target = JamTarget::circular(vec![node("Mallory"), node("Bob"), node("Charlie")], 1, 2);
+        assert_eq!(target.route, vec![
+            node("Mallory"), node("Bob"), node("Charlie"), node("Mallory"), node("Bob"), node("Charlie"), node("Mallory"),
+        ]);
+        assert_eq!(target.hops, vec![1, 4]);
+        for hop in &target.hops {
+            assert_eq!((&target.route[*hop], &target.route[*hop + 1]), (&node("Bob"), &node("Charlie")));
+        }
+        assert_eq!(target.get_sender(), target.get_receiver());
+        assert_eq!(target.get_num_slots_per_payment(), 2);
+    }
+}
@@ -0,0 +1,209 @@
+use crate::common::{
+    duration::Duration,
+    fee::FeeType,
+    nodeid::NodeId,
+    params,
+    satoshi::Satoshi,
+    timestamp::Timestamp,
+};
+use crate::simulator::Simulator as Simulator;
+
+use super::coalition::Coalition as Coalition;
+
+#[derive(Debug, Clone)]
+pub struct AttackCostParams {
+    // on-chain fees to open and close the attacker's channels
+    pub on_chain_cost: Satoshi,
+    // what the attacker could have earned elsewhere on its locked funds, per satoshi per time unit
+    pub capital_cost_rate: f64,
+}
+
+// What an attack cost the attacker, and what damage it did,
+// compared to the same simulation without the attack (the baseline).
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttackReport {
+    // how long the attack was active within the simulation: damage per time is normalized by this
+    pub duration: Duration,
+    // fees paid to nodes outside the coalition
+    pub upfront_fees_paid: Satoshi,
+    // normally zero: jams fail
+    pub success_fees_paid: Satoshi,
+    // hold and reverse hold fees
+    pub hold_fees_paid: Satoshi,
+    // fees earned back by the coalition's own nodes (e.g., its receiver or intermediate nodes)
+    pub fees_recovered: Satoshi,
+    pub on_chain_cost: Satoshi,
+    pub capital_cost: Satoshi,
+    // satoshi-hours the attackers' HTLCs held at their targets
+    pub liquidity_hours_locked: f64,
+    pub num_honest_failed: u64,
+    pub num_honest_failed_baseline: u64,
+    pub victim_revenue: i64,
+    pub victim_revenue_baseline: i64,
+}
+
+impl AttackReport {
+    pub fn new(
+        coalition: &Coalition,
+        cost_params: &AttackCostParams,
+        attacked: &Simulator,
+        baseline: &Simulator,
+        victims: &[NodeId],
+    ) -> Self {
+        let network = attacked.get_network();
+        let coalition_report = coalition.get_report(network);
+        let active_intervals: Vec<(Timestamp, Timestamp)> = attacked
+            .get_attackers()
+            .iter()
+            .filter_map(|attacker| attacker.get_active_interval(network))
+            .collect();
+        let duration = match (
+            active_intervals.iter().map(|(start, _)| start).min(),
+            active_intervals.iter().map(|(_, end)| end).max(),
+        ) {
+            (Some(start), Some(end)) => Duration(end.min(attacked.get_end_time()).0.saturating_sub(start.0)),
+            _ => Duration(0),
+        };
+        let victim_revenue = |simulator: &Simulator| victims
+            .iter()
+            .map(|victim| simulator.get_network().get_revenue(victim).total())
+            .sum();
+        AttackReport {
+            duration,
+            upfront_fees_paid: coalition_report.get_fees_paid(FeeType::Upfront),
+            success_fees_paid: coalition_report.get_fees_paid(FeeType::Success),
+            hold_fees_paid: coalition_report.get_fees_paid(FeeType::Hold)
+                .add(&coalition_report.get_fees_paid(FeeType::ReverseHold)),
+            fees_recovered: coalition_report.fees_earned,
+            on_chain_cost: cost_params.on_chain_cost,
+            capital_cost: Satoshi((coalition_report.capital_locked as f64 * cost_params.capital_cost_rate) as u64),
+            liquidity_hours_locked: attacked
+                .get_attackers()
+                .iter()
+                .map(|attacker| attacker.get_liquidity_locked(network) as f64 / params::TIME_UNITS_PER_HOUR as f64)
+                .sum(),
+            num_honest_failed: attacked.get_stats().num_failed,
+            num_honest_failed_baseline: baseline.get_stats().num_failed,
+            victim_revenue: victim_revenue(attacked),
+            victim_revenue_baseline: victim_revenue(baseline),
+        }
+    }
+
+    pub fn get_total_cost(&self) -> i64 {
+        // may be negative if the coalition earns more than it pays
+        let fees_paid = self.upfront_fees_paid.add(&self.success_fees_paid).add(&self.hold_fees_paid);
+        fees_paid.0 as i64 - self.fees_recovered.0 as i64 + self.on_chain_cost.0 as i64 + self.capital_cost.0 as i64
+    }
+
+    pub fn get_num_extra_failures(&self) -> u64 {
+        self.num_honest_failed.saturating_sub(self.num_honest_failed_baseline)
+    }
+
+    pub fn get_victim_revenue_lost(&self) -> i64 {
+        self.victim_revenue_baseline - self.victim_revenue
+    }
+
+    fn per_time(&self, damage: f64) -> f64 {
+        // an attack that was never active did no damage over time
+        if self.duration.0 > 0 { damage / self.duration.0 as f64 } else { 0.0 }
+    }
+
+    pub fn get_failures_per_time(&self) -> f64 {
+        self.per_time(self.get_num_extra_failures() as f64)
+    }
+
+    pub fn get_revenue_lost_per_time(&self) -> f64 {
+        self.per_time(self.get_victim_revenue_lost() as f64)
+    }
+
+    pub fn get_cost_to_damage_ratio(&self) -> Option<f64> {
+        // how much the attacker pays per satoshi of revenue the victims lose
+        let revenue_lost = self.get_victim_revenue_lost();
+        (revenue_lost > 0).then(|| self.get_total_cost() as f64 / revenue_lost as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attacker::JamTarget;
+    use crate::attacker::adaptive::{AdaptiveJammer, AdaptiveJammerParams, JamMode};
+    use crate::channel::channelindirection::ChannelInDirection;
+    use crate::common::paymentresult::PaymentResult;
+    use crate::network::Network;
+    use crate::schedule::event::Event;
+    use crate::schedule::schedule::Schedule;
+    use crate::testutils::{self, flat_fee, node};
+
+    fn add_channel(network: &mut Network, from: &str, to: &str, num_slots: usize) {
+        testutils::add_channel(network, from, to, ChannelInDirection::new(Some(num_slots), flat_fee(1), flat_fee(5), None, None));
+    }
+
+    fn simulator(end_time: u64) -> Simulator {
+        // Alice pays Dave via Bob and Charlie, Mallory jams Bob - Charlie with payments to her node Eve
+        let mut network = Network::new(None);
+        add_channel(&mut network, "Alice", "Bob", 10);
+        add_channel(&mut network, "Mallory", "Bob", 10);
+        add_channel(&mut network, "Bob", "Charlie", 2);
+        add_channel(&mut network, "Charlie", "Dave", 10);
+        add_channel(&mut network, "Charlie", "Eve", 10);
+        let mut schedule = Schedule::new(Timestamp(end_time));
+        for time in [0, 50] {
+            let event = Event::new(
+                node("Alice"), node("Dave"), Satoshi(100 + time), Duration(5), PaymentResult::SUCCESS, None);
+            schedule.put_event(Timestamp(time), event, None);
+        }
+        Simulator::new(network, schedule, None, 0)
+    }
+
+    fn attack(end_time: u64) -> (Simulator, Simulator, Coalition) {
+        // Mallory jams Bob - Charlie for 100 time units, while Alice pays Dave twice
+        let mut baseline = simulator(end_time);
+        baseline.run();
+        let mut attacked = simulator(end_time);
+        let target = JamTarget::new(vec![node("Mallory"), node("Bob"), node("Charlie"), node("Eve")], 1);
+        let attacker = AdaptiveJammer::new(vec![target], AdaptiveJammerParams {
+            probe_amount: Satoshi(1),
+            jam_mode: JamMode::Slot(Satoshi(1000)),
+            jam_hold_time: Duration(100),
+            budget: Satoshi(1000),
+        });
+        let coalition = attacker.get_coalition().clone();
+        attacked.add_attacker(Box::new(attacker));
+        attacked.run();
+        (attacked, baseline, coalition)
+    }
+
+    #[test]
+    fn report_cost_and_damage() {
+        let (attacked, baseline, coalition) = attack(100);
+        let cost_params = AttackCostParams { on_chain_cost: Satoshi(50), capital_cost_rate: 0.0001 };
+        let report = AttackReport::new(&coalition, &cost_params, &attacked, &baseline, &[node("Bob"), node("Charlie")]);
+        // both honest payments fail
+        assert_eq!(report.num_honest_failed, 2);
+        assert_eq!(report.num_honest_failed_baseline, 0);
+        assert_eq!(report.get_failures_per_time(), 0.02);
+        // jams and probes never succeed, Eve earns back the last hop's upfront fees
+        assert_eq!(report.success_fees_paid, Satoshi(0));
+        assert_eq!(report.upfront_fees_paid, Satoshi(18));
+        assert_eq!(report.fees_recovered, Satoshi(4));
+        // two jams of about 1000 held for 100 time units
+        assert_eq!(report.capital_cost, Satoshi(20));
+        assert_eq!(report.get_total_cost(), 18 - 4 + 50 + 20);
+        // each jam locks 1005 (including the last hop's fees) at Bob - Charlie
+        assert_eq!(report.liquidity_hours_locked, (2 * 1005 * 100) as f64 / 3600.0);
+        // Bob and Charlie lose the honest success fees, but earn upfront fees from the jams
+        assert_eq!(report.get_victim_revenue_lost(), 4);
+        assert_eq!(report.get_cost_to_damage_ratio(), Some(21.0));
+    }
+
+    #[test]
+    fn report_damage_over_active_interval() {
+        // the simulation goes on long after the jams have been released
+        let (attacked, baseline, coalition) = attack(400);
+        let cost_params = AttackCostParams { on_chain_cost: Satoshi(0), capital_cost_rate: 0.0 };
+        let report = AttackReport::new(&coalition, &cost_params, &attacked, &baseline, &[node("Bob"), node("Charlie")]);
+        assert_eq!(report.duration, Duration(100));
+        assert_eq!(report.get_failures_per_time(), 0.02);
+    }
+}
@@ -0,0 +1,70 @@
+use crate::common::duration::Duration as Duration;
+use crate::common::satoshi::Satoshi as Satoshi;
+
+// Slots and liquidity of a channel direction can be split into buckets,
+// as in the HTLC endorsement and bucketing mitigation proposals.
+// Every HTLC goes to the general bucket if it fits there.
+// Otherwise, endorsed HTLCs may use the protected bucket,
+// and unendorsed HTLCs may use the congestion bucket (at most once per window).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Bucket {
+    General,
+    Congestion,
+    Protected,
+}
+
+#[derive(Debug, Clone)]
+pub struct BucketParams {
+    // the shares of slots and liquidity reserved for the congestion and protected buckets,
+    // the rest goes to the general bucket
+    pub congestion_slots_share: f64,
+    pub congestion_liquidity_share: f64,
+    pub protected_slots_share: f64,
+    pub protected_liquidity_share: f64,
+    // the upstream peer may only add an HTLC to the congestion bucket once per this window
+    pub congestion_window: Duration,
+}
+
+impl Default for BucketParams {
+    fn default() -> Self {
+        // 40% general, 20% congestion, 40% protected
+        BucketParams {
+            congestion_slots_share: 0.2,
+            congestion_liquidity_share: 0.2,
+            protected_slots_share: 0.4,
+            protected_liquidity_share: 0.4,
+            congestion_window: Duration(90),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BucketLimits {
+    pub num_slots: usize,
+    // None means the bucket is only limited by slots
+    pub liquidity: Option<Satoshi>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BucketOccupancy {
+    pub num_htlcs: usize,
+    pub amount: Satoshi,
+}
+
+impl Default for BucketOccupancy {
+    fn default() -> Self {
+        BucketOccupancy {
+            num_htlcs: 0,
+            amount: Satoshi(0),
+        }
+    }
+}
+
+impl BucketOccupancy {
+    pub fn add(&self, other: &BucketOccupancy) -> BucketOccupancy {
+        BucketOccupancy {
+            num_htlcs: self.num_htlcs + other.num_htlcs,
+            amount: self.amount.add(&other.amount),
+        }
+    }
+}
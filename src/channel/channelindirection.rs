@@ -1,41 +1,80 @@
 use crate::common::satoshi::Satoshi as Satoshi;
 use crate::common::fee::{Fee as Fee, FeeType as FeeType};
+use crate::common::feepolicy::{FeeContext as FeeContext, FeePolicy as FeePolicy, InboundFee as InboundFee, Occupancy as Occupancy};
+use crate::common::holdfee::HoldFee as HoldFee;
 use crate::common::timestamp::{Timestamp as Timestamp, self};
 use crate::common::params as params;
 use crate::common::errortype::ErrorType as ErrorType;
+use crate::common::paymentid::PaymentId as PaymentId;
+use crate::common::paymentresult::PaymentResult as PaymentResult;
+use crate::common::duration::Duration as Duration;
 
+use super::bucket::{Bucket as Bucket, BucketLimits as BucketLimits, BucketOccupancy as BucketOccupancy, BucketParams as BucketParams};
 use super::htlc::Htlc as Htlc;
 
 use priority_queue::PriorityQueue;
+use rand::Rng;
 use std::cmp::Reverse;
+use std::collections::BTreeMap;
 use std::os::linux::raw;
 
 #[derive(Debug)]
 pub struct ChannelInDirection {
-    pub upfront_fee: Fee,
-    pub success_fee: Fee,
+    pub upfront_fee: Box<dyn FeePolicy>,
+    pub success_fee: Box<dyn FeePolicy>,
+    // if set, charged for the time HTLCs are held, on top of the upfront fee
+    pub hold_fee: Option<HoldFee>,
+    // if set, the downstream node pays the upstream node for the time it holds HTLCs
+    pub reverse_hold_fee: Option<HoldFee>,
+    // set by the downstream node for HTLCs it forwards or receives via this channel direction
+    pub inbound_upfront_fee: Option<InboundFee>,
+    pub inbound_success_fee: Option<InboundFee>,
     pub deliberately_fail_prob: f64,    // can I define a sub-type of float for probabilities to check 0<=x<=1?
     pub spoofing_error_type: ErrorType,
     // we don't store num_slots separately
     // it's better to obtain this data from the slots queue directly
     slots: PriorityQueue<Htlc, Reverse<Timestamp>>,
+    // the channel capacity, a reference for how much of it is in flight when pricing congestion
+    capacity: Option<Satoshi>,
+    // if set, how much may be in flight at once: the balance of the sending side
+    liquidity: Option<Satoshi>,
+    // by default, all slots are in the general bucket and liquidity is not limited
+    bucket_limits: BTreeMap<Bucket, BucketLimits>,
+    congestion_window: Duration,
+    last_congestion_time: Option<Timestamp>,
 }
 
 impl ChannelInDirection {
 
     pub fn new(
         num_slots: Option<usize>,
-        upfront_fee: Option<Fee>,
-        success_fee: Option<Fee>,
+        upfront_fee: Option<Box<dyn FeePolicy>>,
+        success_fee: Option<Box<dyn FeePolicy>>,
         deliberately_fail_prob: Option<f64>,
         spoofing_error_type: Option<ErrorType>
     ) -> Self {
+        let deliberately_fail_prob = deliberately_fail_prob.unwrap_or(0.0);
+        assert!((0.0..=1.0).contains(&deliberately_fail_prob),
+        "Deliberate failure probability must be in [0, 1], got {}", deliberately_fail_prob);
         ChannelInDirection {
-            upfront_fee: upfront_fee.unwrap_or(Fee::default()),
-            success_fee: success_fee.unwrap_or(Fee::default()),
-            deliberately_fail_prob: deliberately_fail_prob.unwrap_or(0.0),
-            spoofing_error_type: spoofing_error_type.unwrap_or(ErrorType::LowBalance),
+            upfront_fee: upfront_fee.unwrap_or(Box::new(Fee::default())),
+            success_fee: success_fee.unwrap_or(Box::new(Fee::default())),
+            hold_fee: None,
+            reverse_hold_fee: None,
+            inbound_upfront_fee: None,
+            inbound_success_fee: None,
+            deliberately_fail_prob,
+            // by default, a deliberately failing node is honest about the reason
+            spoofing_error_type: spoofing_error_type.unwrap_or(ErrorType::FailedDeliberately),
             slots: Self::create_slots_queue(num_slots),
+            capacity: None,
+            liquidity: None,
+            bucket_limits: BTreeMap::from([(
+                Bucket::General,
+                BucketLimits { num_slots: num_slots.unwrap_or(params::MAX_NUM_SLOTS), liquidity: None },
+            )]),
+            congestion_window: Duration(0),
+            last_congestion_time: None,
             }
     }
 
@@ -60,17 +99,36 @@ impl ChannelInDirection {
         self.slots = Self::create_slots_queue(num_slots);
     }
 
-    pub fn set_fee(&mut self, fee_type: FeeType, fee: Fee) {
+    pub fn set_fee(&mut self, fee_type: FeeType, fee: Box<dyn FeePolicy>) {
         match fee_type {
             FeeType::Success => self.success_fee = fee,
             FeeType::Upfront => self.upfront_fee = fee,
+            FeeType::Hold | FeeType::ReverseHold => {
+                panic!("Hold fees are not fee policies, set hold_fee or reverse_hold_fee instead")
+            }
+        }
+    }
+
+    pub fn get_hold_fee_at_resolution(&self) -> Option<&HoldFee> {
+        // a hold fee prepaid with the upfront fee is not charged again at resolution
+        match self.upfront_fee.get_prepaid_hold_fee() {
+            Some(_) => None,
+            None => self.hold_fee.as_ref(),
         }
     }
 
     pub fn requires_fee(&self, fee_type: FeeType, amount: &Satoshi) -> Satoshi {
+        // the fee for an HTLC added now and resolved right away
+        let success_fee_context = FeeContext {
+            occupancy: self.get_occupancy(),
+            ..FeeContext::new(*amount)
+        };
+        let success_fee = self.success_fee.get_fee(&success_fee_context);
         match fee_type {
-            FeeType::Success => self.success_fee.apply(&amount),
-            FeeType::Upfront => self.upfront_fee.apply(&amount),
+            FeeType::Success => success_fee,
+            FeeType::Upfront => self.upfront_fee.get_fee(&FeeContext { success_fee, ..success_fee_context }),
+            // nothing is owed for an HTLC that isn't held
+            FeeType::Hold | FeeType::ReverseHold => Satoshi(0),
         }
     }
 
@@ -100,6 +158,140 @@ impl ChannelInDirection {
         self.slots.capacity() - self.get_num_slots_busy()
     }
 
+    pub fn set_capacity(&mut self, capacity: Satoshi) {
+        self.capacity = Some(capacity);
+    }
+
+    pub fn set_liquidity(&mut self, liquidity: Satoshi) {
+        self.liquidity = Some(liquidity);
+    }
+
+    pub fn get_liquidity_in_flight(&self) -> Satoshi {
+        // Note: as with busy slots, some HTLCs may be outdated
+        self.slots.iter().fold(Satoshi(0), |total, (htlc, _)| total.add(&htlc.amount))
+    }
+
+    pub fn get_available_liquidity(&self, time: &Timestamp) -> Option<Satoshi> {
+        // What is left for new HTLCs: outdated HTLCs are about to be released,
+        // but those that succeed are about to move their amount to the other side, so they still count.
+        let in_flight = self.slots
+            .iter()
+            .filter(|(htlc, resolution_time)| &resolution_time.0 > time || htlc.desired_result == PaymentResult::SUCCESS)
+            .fold(Satoshi(0), |total, (htlc, _)| total.add(&htlc.amount));
+        self.liquidity.map(|liquidity| if in_flight.lt(&liquidity) { liquidity.sub(&in_flight) } else { Satoshi(0) })
+    }
+
+    pub fn get_occupancy(&self) -> Occupancy {
+        // without a known capacity, only slots count
+        let liquidity = match self.capacity {
+            Some(capacity) if capacity.0 > 0 => {
+                (self.get_liquidity_in_flight().0 as f64 / capacity.0 as f64).min(1.0)
+            }
+            _ => 0.0,
+        };
+        Occupancy {
+            slots: self.get_num_slots_busy() as f64 / self.slots.capacity() as f64,
+            liquidity,
+        }
+    }
+
+    pub fn set_buckets(&mut self, bucket_params: &BucketParams, liquidity: Satoshi) {
+        // split the slots and the given liquidity between the buckets as per their shares
+        for share in [
+            bucket_params.congestion_slots_share,
+            bucket_params.congestion_liquidity_share,
+            bucket_params.protected_slots_share,
+            bucket_params.protected_liquidity_share,
+        ] {
+            assert!((0.0..=1.0).contains(&share), "Bucket share must be in [0, 1], got {}", share);
+        }
+        assert!(bucket_params.congestion_slots_share + bucket_params.protected_slots_share <= 1.0,
+        "Congestion and protected buckets can't have more than all slots");
+        assert!(bucket_params.congestion_liquidity_share + bucket_params.protected_liquidity_share <= 1.0,
+        "Congestion and protected buckets can't have more than all liquidity");
+        let num_slots = self.slots.capacity();
+        let num_congestion_slots = (num_slots as f64 * bucket_params.congestion_slots_share) as usize;
+        let num_protected_slots = (num_slots as f64 * bucket_params.protected_slots_share) as usize;
+        let congestion_liquidity = liquidity.mul(bucket_params.congestion_liquidity_share);
+        let protected_liquidity = liquidity.mul(bucket_params.protected_liquidity_share);
+        self.bucket_limits = BTreeMap::from([
+            (Bucket::General, BucketLimits {
+                num_slots: num_slots - num_congestion_slots - num_protected_slots,
+                liquidity: Some(liquidity.sub(&congestion_liquidity).sub(&protected_liquidity)),
+            }),
+            (Bucket::Congestion, BucketLimits {
+                num_slots: num_congestion_slots,
+                liquidity: Some(congestion_liquidity),
+            }),
+            (Bucket::Protected, BucketLimits {
+                num_slots: num_protected_slots,
+                liquidity: Some(protected_liquidity),
+            }),
+        ]);
+        self.congestion_window = bucket_params.congestion_window.clone();
+    }
+
+    pub fn get_bucket_limits(&self, bucket: &Bucket) -> BucketLimits {
+        // buckets that haven't been set up have no room at all
+        self.bucket_limits
+            .get(bucket)
+            .copied()
+            .unwrap_or(BucketLimits { num_slots: 0, liquidity: Some(Satoshi(0)) })
+    }
+
+    pub fn get_bucket_occupancy(&self, bucket: &Bucket, time: &Timestamp) -> BucketOccupancy {
+        // outdated HTLCs don't count: they are about to be released
+        self.slots
+            .iter()
+            .filter(|(htlc, resolution_time)| &htlc.bucket == bucket && &resolution_time.0 > time)
+            .fold(BucketOccupancy::default(), |occupancy, (htlc, _)| {
+                occupancy.add(&BucketOccupancy { num_htlcs: 1, amount: htlc.amount })
+            })
+    }
+
+    pub fn get_num_unendorsed_htlcs_in_flight(&self, time: &Timestamp) -> usize {
+        self.slots
+            .iter()
+            .filter(|(htlc, resolution_time)| !htlc.endorsed && &resolution_time.0 > time)
+            .count()
+    }
+
+    fn has_room_in_bucket(&self, bucket: &Bucket, time: &Timestamp, amount: &Satoshi) -> (bool, bool) {
+        // return whether the bucket has a free slot and whether it has enough liquidity
+        let limits = self.get_bucket_limits(bucket);
+        let occupancy = self.get_bucket_occupancy(bucket, time);
+        let has_slot = occupancy.num_htlcs < limits.num_slots;
+        let has_liquidity = limits.liquidity.is_none_or(|liquidity| occupancy.amount.add(amount).le(&liquidity));
+        (has_slot, has_liquidity)
+    }
+
+    fn can_use_congestion_bucket(&self, time: &Timestamp) -> bool {
+        self.last_congestion_time
+            .as_ref()
+            .is_none_or(|last_time| time >= &last_time.add(&self.congestion_window))
+    }
+
+    fn choose_bucket(&self, time: &Timestamp, htlc: &Htlc) -> Result<Bucket, ErrorType> {
+        // Every HTLC may use the general bucket.
+        // If it is full, endorsed HTLCs fall back to the protected bucket,
+        // and unendorsed ones to the congestion bucket, if the peer hasn't used it recently.
+        let mut candidates = vec![Bucket::General];
+        if htlc.endorsed {
+            candidates.push(Bucket::Protected);
+        } else if self.can_use_congestion_bucket(time) {
+            candidates.push(Bucket::Congestion);
+        }
+        let mut lacks_only_liquidity = false;
+        for bucket in candidates {
+            match self.has_room_in_bucket(&bucket, time, &htlc.amount) {
+                (true, true) => return Ok(bucket),
+                (true, false) => lacks_only_liquidity = true,
+                _ => {},
+            }
+        }
+        Err(if lacks_only_liquidity { ErrorType::LowBalance } else { ErrorType::NoSlots })
+    }
+
     pub fn all_slots_busy(&self) -> bool {
         self.get_num_slots_free() == 0
     }
@@ -122,6 +314,34 @@ impl ChannelInDirection {
         }
     }
 
+    pub fn remove_htlc(&mut self, htlc: &Htlc) -> Option<(Htlc, Timestamp)> {
+        // used to release the HTLC of a payment that failed downstream
+        self.slots.remove(htlc).map(|(htlc, resolution_time)| (htlc, resolution_time.0))
+    }
+
+    pub fn get_htlc_resolution_time(&self, htlc: &Htlc) -> Option<&Timestamp> {
+        self.slots.get_priority(htlc).map(|resolution_time| &resolution_time.0)
+    }
+
+    pub fn postpone_htlc(&mut self, htlc: &Htlc, resolution_time: Timestamp) {
+        // used when the HTLC's payment got queued further downstream
+        let old_resolution_time = self.slots.change_priority(htlc, Reverse(resolution_time));
+        assert!(old_resolution_time.is_some(), "HTLC {:?} not found", htlc);
+    }
+
+    pub fn get_htlcs(&self) -> impl Iterator<Item = (&Htlc, &Timestamp)> {
+        self.slots.iter().map(|(htlc, resolution_time)| (htlc, &resolution_time.0))
+    }
+
+    pub fn remove_htlcs_of_payment(&mut self, payment_id: &PaymentId) -> Vec<(Htlc, Timestamp)> {
+        let htlcs: Vec<Htlc> = self.slots
+            .iter()
+            .filter(|(htlc, _)| &htlc.payment_id == payment_id)
+            .map(|(htlc, _)| htlc.clone())
+            .collect();
+        htlcs.iter().filter_map(|htlc| self.remove_htlc(htlc)).collect()
+    }
+
     pub fn get_earliest_htlc_resolution_time(&self) -> &Timestamp {
         assert!(!self.all_slots_free());
         // .1 takes reversed timestamp from (htlc, reversed timestamp) tuple
@@ -132,7 +352,8 @@ impl ChannelInDirection {
     fn ensure_free_slots(
         &mut self,
         time: &Timestamp,
-        num_slots_needed: usize
+        num_slots_needed: usize,
+        in_flight_payment: Option<&PaymentId>,
     ) -> (bool, Vec<(Htlc, Timestamp)>) {
         // Comment from Python implementation:
 		// # Ensure there are num_slots_needed free slots in the HTLC queue.
@@ -143,29 +364,105 @@ impl ChannelInDirection {
 		// # Return success (True / False) and the released HTLCs, if any, along with their timestamps.
         // TODO: rethink this logic?
         // May seem wasteful that we release _all_ HTLC we can when we need just one
-        // This is related to potential circular routes, AFAIU
-        // If we prohibit loops, we can pop and resolve one by one if needed.
-        // However, routes with loops are useful in attack simulations.
+        // This is related to circular routes:
+        // a payment along a route with loops holds several HTLCs in the same channel direction.
+        // If we prohibited loops, we could pop and resolve one by one if needed.
+        // However, routes with loops are useful in attack simulations (see attacker::JamTarget::circular).
         // For now, we simply translate Python code, maybe optimize later.
+        // The HTLCs of the payment being forwarded are never released, even if outdated
+        // (e.g., a probe along a circular route): the payment may still fail and unwind them.
         let num_free_slots = self.get_num_slots_free();
         if num_free_slots >= num_slots_needed {
             return (true, Vec::<(Htlc, Timestamp)>::new());
-        } else {
-            let num_htlcs_to_release = num_slots_needed - num_free_slots;
-            let mut released_htlcs: Vec<(Htlc, Timestamp)> = Vec::new();
-            for _ in 1..=num_htlcs_to_release {
-                if self.get_earliest_htlc_resolution_time() <= time {
-                    // TODO: isolate the priority queue functionality into a separate module
-                    released_htlcs.push(self.pop_htlc());
-                } else {
-                    for (htlc, timestamp) in released_htlcs {
-                        self.push_htlc(timestamp, htlc);
-                    }
-                    return (false, Vec::<(Htlc, Timestamp)>::new());
+        }
+        let num_htlcs_to_release = num_slots_needed - num_free_slots;
+        let mut released_htlcs: Vec<(Htlc, Timestamp)> = Vec::new();
+        let mut kept_htlcs: Vec<(Htlc, Timestamp)> = Vec::new();
+        while released_htlcs.len() < num_htlcs_to_release
+            && !self.all_slots_free()
+            && self.get_earliest_htlc_resolution_time() <= time
+        {
+            // TODO: isolate the priority queue functionality into a separate module
+            let (htlc, timestamp) = self.pop_htlc();
+            if in_flight_payment == Some(&htlc.payment_id) {
+                kept_htlcs.push((htlc, timestamp));
+            } else {
+                released_htlcs.push((htlc, timestamp));
+            }
+        }
+        for (htlc, timestamp) in kept_htlcs {
+            self.push_htlc(timestamp, htlc);
+        }
+        if released_htlcs.len() < num_htlcs_to_release {
+            for (htlc, timestamp) in released_htlcs {
+                self.push_htlc(timestamp, htlc);
+            }
+            return (false, Vec::<(Htlc, Timestamp)>::new());
+        }
+        (true, released_htlcs)
+    }
+
+    pub fn fails_deliberately<R: Rng>(&self, rng: &mut R) -> bool {
+        // gen_bool(0.0) is always false, gen_bool(1.0) is always true
+        rng.gen_bool(self.deliberately_fail_prob)
+    }
+
+    pub fn add_htlc<R: Rng>(
+        &mut self,
+        time: &Timestamp,
+        resolution_time: Timestamp,
+        htlc: &mut Htlc,
+        rng: &mut R,
+    ) -> Result<Vec<(Htlc, Timestamp)>, ErrorType> {
+        self.add_htlc_if_admitted(time, resolution_time, htlc, rng, || Ok(Duration(0)))
+    }
+
+    pub fn add_htlc_if_admitted<R: Rng>(
+        &mut self,
+        time: &Timestamp,
+        resolution_time: Timestamp,
+        htlc: &mut Htlc,
+        rng: &mut R,
+        admit: impl FnOnce() -> Result<Duration, ErrorType>,
+    ) -> Result<Vec<(Htlc, Timestamp)>, ErrorType> {
+        // Try to store an HTLC in this channel direction, and set the bucket it is stored in.
+        // On success, return the outdated HTLCs released to make room for it:
+        // the caller is responsible for resolving them.
+        // A malicious node decides to fail before touching the slots,
+        // so no released HTLCs get lost if it does.
+        // The true reason never leaves this hop:
+        // upstream nodes and the sender only see the spoofed error type.
+        if self.fails_deliberately(rng) {
+            return Err(self.spoofing_error_type);
+        }
+        // whatever the buckets, no more than the channel direction's liquidity may be locked
+        if self.get_available_liquidity(time).is_some_and(|available| htlc.amount.gt(&available)) {
+            return Err(ErrorType::LowBalance);
+        }
+        let bucket = self.choose_bucket(time, htlc)?;
+        let (success, released_htlcs) = self.ensure_free_slots(time, 1, Some(&htlc.payment_id));
+        if !success {
+            return Err(ErrorType::NoSlots);
+        }
+        // Only an HTLC that fits is up to the downstream node's circuit breaker (if any),
+        // which may turn it down, or queue it:
+        // a queued HTLC occupies its slot for longer, and so does its payment at every hop.
+        let queue_delay = match admit() {
+            Ok(queue_delay) => queue_delay,
+            Err(error_type) => {
+                // the released HTLCs stay until someone else needs their slots
+                for (released_htlc, released_resolution_time) in released_htlcs {
+                    self.push_htlc(released_resolution_time, released_htlc);
                 }
+                return Err(error_type);
             }
-            (true, released_htlcs)
+        };
+        if bucket == Bucket::Congestion {
+            self.last_congestion_time = Some(time.clone());
         }
+        htlc.bucket = bucket;
+        self.push_htlc(resolution_time.add(&queue_delay), htlc.clone());
+        Ok(released_htlcs)
     }
 
 
@@ -174,16 +471,16 @@ impl ChannelInDirection {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::common::paymentresult::PaymentResult as PaymentResult;
     use crate::common::satoshi::Satoshi as Satoshi;
     use crate::common::fee::FeeRate as FeeRate;
+    use rand::{rngs::StdRng, SeedableRng};
 
     #[test]
     pub fn channelindirection_assign_fee() {
         let mut ch_in_dir = ChannelInDirection::new(
             None,
-            Some(Fee::new(Satoshi(5), FeeRate(0.02))),
-            Some(Fee::new(Satoshi(1), FeeRate(0.03))),
+            Some(Box::new(Fee::new(Satoshi(5), FeeRate(0.02)))),
+            Some(Box::new(Fee::new(Satoshi(1), FeeRate(0.03)))),
             None,
             None,
         );
@@ -242,6 +539,7 @@ mod tests {
         assert_queue_is_empty(&ch_in_dir);
         // push one HTLC
         let htlc1 = Htlc::new(
+            PaymentId::new(),
             Satoshi(1),
         PaymentResult::SUCCESS,
         );
@@ -253,6 +551,7 @@ mod tests {
         assert_eq!(ch_in_dir.get_earliest_htlc_resolution_time(), &Timestamp(10));
         // push another HTLC
         let htlc2 = Htlc::new(
+            PaymentId::new(),
             Satoshi(2),
         PaymentResult::SUCCESS,
         );
@@ -282,14 +581,17 @@ mod tests {
             Some(4), None, None, None, None,
         );
         let htlc1 = Htlc::new(
+            PaymentId::new(),
             Satoshi(100),
         PaymentResult::SUCCESS,
         );
         let htlc2 = Htlc::new(
+            PaymentId::new(),
             Satoshi(200),
         PaymentResult::SUCCESS,
         );
         let htlc3 = Htlc::new(
+            PaymentId::new(),
             Satoshi(300),
         PaymentResult::SUCCESS,
         );
@@ -298,38 +600,302 @@ mod tests {
         ch_in_dir.push_htlc(Timestamp(15), htlc3);
         // at time 3, we can ensure one slot, no HTLCs are released
         let (success, released_htlcs) = ch_in_dir.ensure_free_slots(
-            &Timestamp(3), 1);
+            &Timestamp(3), 1, None);
         assert_eq!(success, true);
         assert_eq!(released_htlcs.len(), 0);
         // at time 5, we can ensure one slot, on HTLCs are released 
         let (success, released_htlcs) = ch_in_dir.ensure_free_slots(
-            &Timestamp(5), 1);
+            &Timestamp(5), 1, None);
         assert_eq!(success, true);
         assert_eq!(released_htlcs.len(), 0);
         // at time 6, we can ensure one slot, on HTLCs are released 
         let (success, released_htlcs) = ch_in_dir.ensure_free_slots(
-            &Timestamp(6), 1);
+            &Timestamp(6), 1, None);
         assert_eq!(success, true);
         assert_eq!(released_htlcs.len(), 0);
         // at time 5, we can ensure two slots by releasing the htlc with resolution time 5
         let (success, released_htlcs) = ch_in_dir.ensure_free_slots(
-            &Timestamp(5), 2);
+            &Timestamp(5), 2, None);
         assert_eq!(success, true);
         assert_eq!(released_htlcs.len(), 1);
         // now there are two free slots
         assert_eq!(ch_in_dir.get_num_slots_free(), 2);
         // at time 12, we cannot ensure 4 slots
         let (success, released_htlcs) = ch_in_dir.ensure_free_slots(
-            &Timestamp(12), 4);
+            &Timestamp(12), 4, None);
         assert_eq!(success, false);
         assert_eq!(released_htlcs.len(), 0);
         // at time 12, we can ensure 3 slots
         let (success, released_htlcs) = ch_in_dir.ensure_free_slots(
-            &Timestamp(12), 3);
+            &Timestamp(12), 3, None);
         assert_eq!(success, true);
         assert_eq!(released_htlcs.len(), 1);
         assert_eq!(ch_in_dir.get_num_slots_free(), 3);
 
     }
 
+    #[test]
+    fn channelindirection_ensure_free_slots_keeps_payment_in_flight() {
+        let mut ch_in_dir = ChannelInDirection::new(
+            Some(1), None, None, None, None,
+        );
+        let payment_id = PaymentId::new();
+        ch_in_dir.push_htlc(Timestamp(0), Htlc::new(payment_id.clone(), Satoshi(1), PaymentResult::FAILURE));
+        // the outdated HTLC belongs to the payment being forwarded (along a circular route)
+        let (success, released_htlcs) = ch_in_dir.ensure_free_slots(&Timestamp(0), 1, Some(&payment_id));
+        assert!(!success);
+        assert_eq!(released_htlcs.len(), 0);
+        assert_eq!(ch_in_dir.get_num_slots_busy(), 1);
+        // any other payment may have the slot
+        let (success, released_htlcs) = ch_in_dir.ensure_free_slots(&Timestamp(0), 1, None);
+        assert!(success);
+        assert_eq!(released_htlcs.len(), 1);
+    }
+
+    #[test]
+    fn channelindirection_remove_htlc() {
+        let mut ch_in_dir = ChannelInDirection::new(
+            Some(2), None, None, None, None,
+        );
+        let htlc1 = Htlc::new(PaymentId::new(), Satoshi(1), PaymentResult::SUCCESS);
+        let htlc2 = Htlc::new(PaymentId::new(), Satoshi(1), PaymentResult::SUCCESS);
+        ch_in_dir.push_htlc(Timestamp(5), htlc1.clone());
+        ch_in_dir.push_htlc(Timestamp(10), htlc2.clone());
+        // HTLCs with equal fees are told apart by payment IDs
+        let (removed_htlc, resolution_time) = ch_in_dir.remove_htlc(&htlc2).unwrap();
+        assert_eq!(removed_htlc, htlc2);
+        assert_eq!(resolution_time, Timestamp(10));
+        assert!(ch_in_dir.remove_htlc(&htlc2).is_none());
+        assert_eq!(ch_in_dir.get_num_slots_busy(), 1);
+        assert_eq!(ch_in_dir.get_earliest_htlc_resolution_time(), &Timestamp(5));
+    }
+
+    #[test]
+    fn channelindirection_remove_htlcs_of_payment() {
+        let mut ch_in_dir = ChannelInDirection::new(
+            Some(3), None, None, None, None,
+        );
+        let payment_id = PaymentId::new();
+        ch_in_dir.push_htlc(Timestamp(5), Htlc::new(payment_id.clone(), Satoshi(1), PaymentResult::SUCCESS));
+        ch_in_dir.push_htlc(Timestamp(5), Htlc::new(payment_id.clone(), Satoshi(2), PaymentResult::SUCCESS));
+        ch_in_dir.push_htlc(Timestamp(5), Htlc::new(PaymentId::new(), Satoshi(1), PaymentResult::SUCCESS));
+        assert_eq!(ch_in_dir.remove_htlcs_of_payment(&payment_id).len(), 2);
+        assert_eq!(ch_in_dir.get_num_slots_busy(), 1);
+        assert!(ch_in_dir.remove_htlcs_of_payment(&payment_id).is_empty());
+    }
+
+    fn htlc(amount: u64, endorsed: bool) -> Htlc {
+        Htlc {
+            amount: Satoshi(amount),
+            endorsed,
+            ..Htlc::new(PaymentId::new(), Satoshi(1), PaymentResult::SUCCESS)
+        }
+    }
+
+    fn bucket_params() -> BucketParams {
+        // out of 5 slots: 2 general, 1 congestion, 2 protected
+        BucketParams {
+            congestion_slots_share: 0.2,
+            congestion_liquidity_share: 0.2,
+            protected_slots_share: 0.4,
+            protected_liquidity_share: 0.4,
+            congestion_window: Duration(10),
+        }
+    }
+
+    #[test]
+    fn channelindirection_set_buckets() {
+        let mut ch_in_dir = ChannelInDirection::new(
+            Some(5), None, None, None, None,
+        );
+        // before bucketing, everything is general
+        assert_eq!(ch_in_dir.get_bucket_limits(&Bucket::General), BucketLimits { num_slots: 5, liquidity: None });
+        assert_eq!(ch_in_dir.get_bucket_limits(&Bucket::Protected).num_slots, 0);
+        ch_in_dir.set_buckets(&bucket_params(), Satoshi(1000));
+        assert_eq!(ch_in_dir.get_bucket_limits(&Bucket::General), BucketLimits { num_slots: 2, liquidity: Some(Satoshi(400)) });
+        assert_eq!(ch_in_dir.get_bucket_limits(&Bucket::Congestion), BucketLimits { num_slots: 1, liquidity: Some(Satoshi(200)) });
+        assert_eq!(ch_in_dir.get_bucket_limits(&Bucket::Protected), BucketLimits { num_slots: 2, liquidity: Some(Satoshi(400)) });
+    }
+
+    #[test]
+    fn channelindirection_protected_bucket() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut ch_in_dir = ChannelInDirection::new(
+            Some(4), None, None, None, None,
+        );
+        // out of 4 slots: 2 general, 2 protected
+        let bucket_params = BucketParams { congestion_slots_share: 0.0, protected_slots_share: 0.5, ..bucket_params() };
+        ch_in_dir.set_buckets(&bucket_params, Satoshi(1000));
+        assert!(ch_in_dir.add_htlc(&Timestamp(0), Timestamp(5), &mut htlc(100, false), &mut rng).is_ok());
+        assert!(ch_in_dir.add_htlc(&Timestamp(0), Timestamp(5), &mut htlc(100, false), &mut rng).is_ok());
+        // the general bucket is full, but the protected bucket is only open to endorsed HTLCs
+        let result = ch_in_dir.add_htlc(&Timestamp(0), Timestamp(5), &mut htlc(100, false), &mut rng);
+        assert_eq!(result.unwrap_err(), ErrorType::NoSlots);
+        let mut endorsed_htlc = htlc(100, true);
+        assert!(ch_in_dir.add_htlc(&Timestamp(0), Timestamp(5), &mut endorsed_htlc, &mut rng).is_ok());
+        assert_eq!(endorsed_htlc.bucket, Bucket::Protected);
+        assert_eq!(
+            ch_in_dir.get_bucket_occupancy(&Bucket::Protected, &Timestamp(0)),
+            BucketOccupancy { num_htlcs: 1, amount: Satoshi(100) },
+        );
+        // once the unendorsed HTLCs are outdated, their general slots can be reused
+        assert_eq!(ch_in_dir.get_bucket_occupancy(&Bucket::General, &Timestamp(5)).num_htlcs, 0);
+        let released_htlcs = ch_in_dir.add_htlc(&Timestamp(5), Timestamp(10), &mut htlc(100, false), &mut rng);
+        assert_eq!(released_htlcs.unwrap().len(), 0);
+        assert!(ch_in_dir.all_slots_busy());
+    }
+
+    #[test]
+    fn channelindirection_congestion_bucket() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut ch_in_dir = ChannelInDirection::new(
+            Some(5), None, None, None, None,
+        );
+        ch_in_dir.set_buckets(&bucket_params(), Satoshi(1000));
+        assert!(ch_in_dir.add_htlc(&Timestamp(0), Timestamp(100), &mut htlc(100, false), &mut rng).is_ok());
+        assert!(ch_in_dir.add_htlc(&Timestamp(0), Timestamp(100), &mut htlc(100, false), &mut rng).is_ok());
+        // the general bucket is full: the peer may use the congestion bucket once per window
+        let mut congestion_htlc = htlc(100, false);
+        assert!(ch_in_dir.add_htlc(&Timestamp(1), Timestamp(2), &mut congestion_htlc, &mut rng).is_ok());
+        assert_eq!(congestion_htlc.bucket, Bucket::Congestion);
+        let result = ch_in_dir.add_htlc(&Timestamp(5), Timestamp(6), &mut htlc(100, false), &mut rng);
+        assert_eq!(result.unwrap_err(), ErrorType::NoSlots);
+        assert!(ch_in_dir.add_htlc(&Timestamp(11), Timestamp(12), &mut htlc(100, false), &mut rng).is_ok());
+    }
+
+    #[test]
+    fn channelindirection_admits_only_htlcs_that_fit() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut ch_in_dir = ChannelInDirection::new(
+            Some(1), None, None, None, None,
+        );
+        let result = ch_in_dir.add_htlc_if_admitted(
+            &Timestamp(0), Timestamp(5), &mut htlc(100, false), &mut rng, || Ok(Duration(2)));
+        assert!(result.is_ok());
+        // queued HTLCs resolve later
+        assert_eq!(ch_in_dir.get_earliest_htlc_resolution_time(), &Timestamp(7));
+        // an HTLC that doesn't fit never gets to the circuit breaker
+        let result = ch_in_dir.add_htlc_if_admitted(
+            &Timestamp(0), Timestamp(5), &mut htlc(200, false), &mut rng, || panic!("Must not be admitted"));
+        assert_eq!(result.unwrap_err(), ErrorType::NoSlots);
+        // a released HTLC stays if the circuit breaker turns the new one down
+        let result = ch_in_dir.add_htlc_if_admitted(
+            &Timestamp(7), Timestamp(10), &mut htlc(300, false), &mut rng, || Err(ErrorType::RateLimited));
+        assert_eq!(result.unwrap_err(), ErrorType::RateLimited);
+        assert_eq!(ch_in_dir.get_num_slots_busy(), 1);
+    }
+
+    #[test]
+    fn channelindirection_bucket_liquidity() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut ch_in_dir = ChannelInDirection::new(
+            Some(5), None, None, None, None,
+        );
+        ch_in_dir.set_buckets(&bucket_params(), Satoshi(1000));
+        // a free general slot doesn't help if the general liquidity is used up
+        assert!(ch_in_dir.add_htlc(&Timestamp(0), Timestamp(5), &mut htlc(300, false), &mut rng).is_ok());
+        let mut large_htlc = htlc(150, false);
+        assert!(ch_in_dir.add_htlc(&Timestamp(0), Timestamp(5), &mut large_htlc, &mut rng).is_ok());
+        assert_eq!(large_htlc.bucket, Bucket::Congestion);
+        let result = ch_in_dir.add_htlc(&Timestamp(0), Timestamp(5), &mut htlc(150, true), &mut rng);
+        assert!(result.is_ok());
+        let result = ch_in_dir.add_htlc(&Timestamp(0), Timestamp(5), &mut htlc(300, true), &mut rng);
+        assert_eq!(result.unwrap_err(), ErrorType::LowBalance);
+    }
+
+    #[test]
+    fn channelindirection_capacity_only_prices_congestion() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut ch_in_dir = ChannelInDirection::new(
+            Some(5), None, None, None, None,
+        );
+        ch_in_dir.set_capacity(Satoshi(1000));
+        // the capacity is no limit on what may be in flight
+        assert!(ch_in_dir.add_htlc(&Timestamp(0), Timestamp(5), &mut htlc(600, false), &mut rng).is_ok());
+        assert!(ch_in_dir.add_htlc(&Timestamp(0), Timestamp(5), &mut htlc(700, false), &mut rng).is_ok());
+        assert_eq!(ch_in_dir.get_available_liquidity(&Timestamp(0)), None);
+        // but it tells how congested the channel direction is
+        assert_eq!(ch_in_dir.get_occupancy(), Occupancy { slots: 0.4, liquidity: 1.0 });
+    }
+
+    #[test]
+    fn channelindirection_available_liquidity() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut ch_in_dir = ChannelInDirection::new(
+            Some(5), None, None, None, None,
+        );
+        assert_eq!(ch_in_dir.get_available_liquidity(&Timestamp(0)), None);
+        ch_in_dir.set_liquidity(Satoshi(1000));
+        // one large HTLC locks most of the liquidity, though most slots are free
+        let mut failing_htlc = Htlc { desired_result: PaymentResult::FAILURE, ..htlc(900, false) };
+        assert!(ch_in_dir.add_htlc(&Timestamp(0), Timestamp(5), &mut failing_htlc, &mut rng).is_ok());
+        assert_eq!(ch_in_dir.get_available_liquidity(&Timestamp(0)), Some(Satoshi(100)));
+        let result = ch_in_dir.add_htlc(&Timestamp(0), Timestamp(5), &mut htlc(200, false), &mut rng);
+        assert_eq!(result.unwrap_err(), ErrorType::LowBalance);
+        // once outdated, the failing HTLC no longer counts
+        assert_eq!(ch_in_dir.get_available_liquidity(&Timestamp(5)), Some(Satoshi(1000)));
+        assert!(ch_in_dir.add_htlc(&Timestamp(5), Timestamp(10), &mut htlc(200, false), &mut rng).is_ok());
+        // but a successful one does until it is settled
+        assert_eq!(ch_in_dir.get_available_liquidity(&Timestamp(10)), Some(Satoshi(800)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn channelindirection_invalid_deliberately_fail_prob() {
+        let _ch_in_dir = ChannelInDirection::new(
+            None, None, None, Some(1.5), None,
+        );
+    }
+
+    #[test]
+    fn channelindirection_add_htlc_honest() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut ch_in_dir = ChannelInDirection::new(
+            Some(1), None, None, None, None,
+        );
+        let mut htlc1 = Htlc::new(PaymentId::new(), Satoshi(1), PaymentResult::SUCCESS);
+        let mut htlc2 = Htlc::new(PaymentId::new(), Satoshi(2), PaymentResult::SUCCESS);
+        let released_htlcs = ch_in_dir.add_htlc(
+            &Timestamp(0), Timestamp(5), &mut htlc1, &mut rng);
+        assert_eq!(released_htlcs.unwrap().len(), 0);
+        // the only slot is busy until time 5
+        let result = ch_in_dir.add_htlc(
+            &Timestamp(3), Timestamp(8), &mut htlc2, &mut rng);
+        assert_eq!(result.unwrap_err(), ErrorType::NoSlots);
+        // at time 5, the first HTLC is released to make room for the second one
+        let mut htlc2 = Htlc::new(PaymentId::new(), Satoshi(2), PaymentResult::SUCCESS);
+        let released_htlcs = ch_in_dir.add_htlc(
+            &Timestamp(5), Timestamp(8), &mut htlc2, &mut rng).unwrap();
+        assert_eq!(released_htlcs.len(), 1);
+        assert_eq!(released_htlcs[0].1, Timestamp(5));
+        assert_eq!(ch_in_dir.get_earliest_htlc_resolution_time(), &Timestamp(8));
+    }
+
+    #[test]
+    fn channelindirection_add_htlc_fails_deliberately() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut ch_in_dir = ChannelInDirection::new(
+            Some(2), None, None, Some(1.0), None,
+        );
+        let mut htlc = Htlc::new(PaymentId::new(), Satoshi(1), PaymentResult::SUCCESS);
+        let result = ch_in_dir.add_htlc(
+            &Timestamp(0), Timestamp(5), &mut htlc, &mut rng);
+        assert_eq!(result.unwrap_err(), ErrorType::FailedDeliberately);
+        assert_queue_is_empty(&ch_in_dir);
+    }
+
+    #[test]
+    fn channelindirection_add_htlc_spoofs_error_type() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut ch_in_dir = ChannelInDirection::new(
+            Some(2), None, None, Some(1.0), Some(ErrorType::NoSlots),
+        );
+        let mut htlc = Htlc::new(PaymentId::new(), Satoshi(1), PaymentResult::SUCCESS);
+        // slots are free, but the node claims they are not
+        let result = ch_in_dir.add_htlc(
+            &Timestamp(0), Timestamp(5), &mut htlc, &mut rng);
+        assert_eq!(result.unwrap_err(), ErrorType::NoSlots);
+        assert_queue_is_empty(&ch_in_dir);
+    }
+
 }
\ No newline at end of file
@@ -0,0 +1,160 @@
+use crate::common::duration::Duration as Duration;
+use crate::common::errortype::ErrorType as ErrorType;
+use crate::common::timestamp::Timestamp as Timestamp;
+
+// A node-side firewall for HTLCs coming in from a peer, as lnd's circuitbreaker.
+// It limits how many HTLCs from the peer may be pending at once,
+// and how fast the peer may add new ones (with a token bucket).
+// Excess HTLCs are either failed right away or queued until the limits let them through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitBreakerMode {
+    Fail,
+    Queue,
+}
+
+#[derive(Debug, Clone)]
+pub struct CircuitBreakerParams {
+    pub max_pending_htlcs: usize,
+    // the token bucket: up to burst HTLCs at once, refilled at rate HTLCs per time unit
+    pub burst: usize,
+    pub rate: f64,
+    pub mode: CircuitBreakerMode,
+}
+
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    params: CircuitBreakerParams,
+    // may go negative in queue mode: queued HTLCs use up tokens that haven't been refilled yet
+    tokens: f64,
+    last_refill_time: Timestamp,
+    num_dropped: u64,
+    num_queued: u64,
+}
+
+impl CircuitBreaker {
+    pub fn new(params: CircuitBreakerParams) -> Self {
+        assert!(params.max_pending_htlcs > 0, "Circuit breaker must allow at least one pending HTLC");
+        assert!(params.burst > 0, "Circuit breaker burst must be at least one HTLC");
+        assert!(params.rate > 0.0, "Circuit breaker rate must be positive, got {}", params.rate);
+        CircuitBreaker {
+            tokens: params.burst as f64,
+            params,
+            last_refill_time: Timestamp(0),
+            num_dropped: 0,
+            num_queued: 0,
+        }
+    }
+
+    pub fn get_num_dropped(&self) -> u64 {
+        self.num_dropped
+    }
+
+    pub fn get_num_queued(&self) -> u64 {
+        self.num_queued
+    }
+
+    fn refill(&mut self, time: &Timestamp) {
+        if time > &self.last_refill_time {
+            let elapsed = (time.0 - self.last_refill_time.0) as f64;
+            self.tokens = (self.tokens + elapsed * self.params.rate).min(self.params.burst as f64);
+            self.last_refill_time = time.clone();
+        }
+    }
+
+    pub fn admit(&mut self, time: &Timestamp, pending_resolution_times: &[Timestamp]) -> Result<Duration, ErrorType> {
+        // Decide on a new HTLC from the peer, given when its pending HTLCs resolve.
+        // Return how long the HTLC has to wait in the queue (zero if it passes right away).
+        self.refill(time);
+        let mut pending_resolution_times: Vec<&Timestamp> = pending_resolution_times
+            .iter()
+            .filter(|resolution_time| resolution_time > &time)
+            .collect();
+        pending_resolution_times.sort();
+        // wait until enough pending HTLCs are resolved to get under the limit
+        let num_pending = pending_resolution_times.len();
+        let pending_delay = if num_pending < self.params.max_pending_htlcs {
+            0
+        } else {
+            pending_resolution_times[num_pending - self.params.max_pending_htlcs].0 - time.0
+        };
+        // wait until the next token is refilled
+        let rate_delay = if self.tokens >= 1.0 {
+            0
+        } else {
+            ((1.0 - self.tokens) / self.params.rate).ceil() as u64
+        };
+        let delay = pending_delay.max(rate_delay);
+        if delay > 0 {
+            match self.params.mode {
+                CircuitBreakerMode::Fail => {
+                    self.num_dropped += 1;
+                    return Err(ErrorType::RateLimited);
+                }
+                CircuitBreakerMode::Queue => self.num_queued += 1,
+            }
+        }
+        self.tokens -= 1.0;
+        Ok(Duration(delay))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(mode: CircuitBreakerMode) -> CircuitBreakerParams {
+        CircuitBreakerParams {
+            max_pending_htlcs: 2,
+            burst: 3,
+            rate: 0.5,
+            mode,
+        }
+    }
+
+    #[test]
+    fn circuitbreaker_fail_mode() {
+        let mut circuit_breaker = CircuitBreaker::new(params(CircuitBreakerMode::Fail));
+        let time = Timestamp(0);
+        assert_eq!(circuit_breaker.admit(&time, &[]), Ok(Duration(0)));
+        assert_eq!(circuit_breaker.admit(&time, &[Timestamp(10)]), Ok(Duration(0)));
+        // too many pending HTLCs
+        let pending = [Timestamp(10), Timestamp(20)];
+        assert_eq!(circuit_breaker.admit(&time, &pending), Err(ErrorType::RateLimited));
+        // resolved HTLCs don't count
+        assert_eq!(circuit_breaker.admit(&Timestamp(10), &pending), Ok(Duration(0)));
+        assert_eq!(circuit_breaker.get_num_dropped(), 1);
+    }
+
+    #[test]
+    fn circuitbreaker_token_bucket() {
+        let mut circuit_breaker = CircuitBreaker::new(params(CircuitBreakerMode::Fail));
+        for _ in 0..3 {
+            assert!(circuit_breaker.admit(&Timestamp(0), &[]).is_ok());
+        }
+        assert!(circuit_breaker.admit(&Timestamp(1), &[]).is_err());
+        // one token is refilled every two time units
+        assert!(circuit_breaker.admit(&Timestamp(2), &[]).is_ok());
+        assert!(circuit_breaker.admit(&Timestamp(2), &[]).is_err());
+        // no more than the burst is accumulated
+        for _ in 0..3 {
+            assert!(circuit_breaker.admit(&Timestamp(100), &[]).is_ok());
+        }
+        assert!(circuit_breaker.admit(&Timestamp(100), &[]).is_err());
+    }
+
+    #[test]
+    fn circuitbreaker_queue_mode() {
+        let mut circuit_breaker = CircuitBreaker::new(params(CircuitBreakerMode::Queue));
+        let time = Timestamp(0);
+        // waits for the earliest pending HTLC to resolve
+        let pending = [Timestamp(20), Timestamp(10)];
+        assert_eq!(circuit_breaker.admit(&time, &pending), Ok(Duration(10)));
+        assert_eq!(circuit_breaker.admit(&time, &[]), Ok(Duration(0)));
+        assert_eq!(circuit_breaker.admit(&time, &[]), Ok(Duration(0)));
+        // waits for tokens: the queued HTLCs wait in turn
+        assert_eq!(circuit_breaker.admit(&time, &[]), Ok(Duration(2)));
+        assert_eq!(circuit_breaker.admit(&time, &[]), Ok(Duration(4)));
+        assert_eq!(circuit_breaker.get_num_queued(), 3);
+        assert_eq!(circuit_breaker.get_num_dropped(), 0);
+    }
+}
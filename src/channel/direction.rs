@@ -1,7 +1,7 @@
 use std::cmp::Ordering;
 use crate::common::nodeid::NodeId as NodeId;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 #[derive(PartialEq)]
 #[derive(Eq)]
 #[derive(Hash)]
@@ -19,6 +19,13 @@ impl Direction {
             }
         }
     }
+
+    pub fn reverse(&self) -> Self {
+        match self {
+            Direction::Alph => Direction::NonAlph,
+            Direction::NonAlph => Direction::Alph,
+        }
+    }
 }
 
 #[cfg(test)]
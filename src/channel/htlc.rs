@@ -1,17 +1,86 @@
 use crate::common::satoshi::Satoshi as Satoshi;
+use crate::common::duration::Duration as Duration;
+use crate::common::nodeid::NodeId as NodeId;
+use crate::common::paymentid::PaymentId as PaymentId;
 use crate::common::paymentresult::PaymentResult as PaymentResult;
+use crate::common::timestamp::Timestamp as Timestamp;
+use crate::common::holdfee::HoldFee as HoldFee;
+use crate::common::trafficclass::TrafficClass as TrafficClass;
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+use super::bucket::Bucket as Bucket;
+
+// HTLCs are keys in the slots queue, so they must be unique:
+// the payment ID tells apart HTLCs with otherwise equal fields
+// and lets us find the HTLC of a given payment when it fails downstream.
+// A circular route may pass the same channel direction several times:
+// the hop tells apart the HTLCs of one payment there.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Htlc {
-    success_fee_satoshi: Satoshi,
-    desired_result: PaymentResult,
+    pub payment_id: PaymentId,
+    // the index of the hop along the payment's route
+    pub hop: usize,
+    pub success_fee_satoshi: Satoshi,
+    pub desired_result: PaymentResult,
+    // only endorsed HTLCs may use protected slots
+    pub endorsed: bool,
+    pub added_at: Timestamp,
+    // what the downstream node earns on success, net of the fees it pays further downstream
+    pub forwarding_fee: Satoshi,
+    // the amount locked in the channel direction while the HTLC is in flight
+    pub amount: Satoshi,
+    // the (cumulative) upfront fee paid on this hop, which may be partly refunded at resolution
+    pub upfront_fee: Satoshi,
+    // set by the channel direction when the HTLC is stored
+    pub bucket: Bucket,
+    // the hold fees the upstream node pays at resolution, for this hop and all hops downstream:
+    // the terms of each hop and the amount they apply to
+    pub hold_fees: Vec<(HoldFee, Satoshi)>,
+    // the reverse hold fees the downstream node pays at resolution, for this hop and all hops upstream
+    pub reverse_hold_fees: Vec<(HoldFee, Satoshi)>,
+    // the traffic class of the payment, to attribute the fees paid at resolution
+    pub traffic_class: TrafficClass,
+    // how long after the receiver resolves the payment this HTLC resolves, as the resolution travels back
+    pub resolution_delay: Duration,
 }
 impl Htlc {
-    // TODO: generate random payment Id if not given
-    pub fn new(success_fee_satoshi: Satoshi, desired_result: PaymentResult) -> Htlc {
+    pub fn new(
+        payment_id: PaymentId,
+        success_fee_satoshi: Satoshi,
+        desired_result: PaymentResult,
+    ) -> Htlc {
         Htlc {
+            payment_id,
+            hop: 0,
             success_fee_satoshi,
             desired_result,
+            endorsed: false,
+            added_at: Timestamp(0),
+            forwarding_fee: Satoshi(0),
+            amount: Satoshi(0),
+            upfront_fee: Satoshi(0),
+            bucket: Bucket::General,
+            hold_fees: Vec::new(),
+            reverse_hold_fees: Vec::new(),
+            traffic_class: TrafficClass::default(),
+            resolution_delay: Duration(0),
         }
     }
-}
\ No newline at end of file
+}
+
+// How long an HTLC kept its amount (and slot) locked in a channel direction:
+// from when it was added until it was resolved, or until it is expected to be if still in flight.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HtlcLock {
+    pub hop: usize,
+    pub from: NodeId,
+    pub to: NodeId,
+    pub amount: Satoshi,
+    pub added_at: Timestamp,
+    pub resolved_at: Timestamp,
+}
+
+impl HtlcLock {
+    pub fn get_amount_time(&self) -> u64 {
+        self.amount.0 * self.resolved_at.0.saturating_sub(self.added_at.0)
+    }
+}
@@ -1,10 +1,13 @@
 use std::collections::HashMap;
 
-mod channelindirection;
-mod direction;
-mod htlc;
+pub mod bucket;
+pub mod channelindirection;
+pub mod circuitbreaker;
+pub mod direction;
+pub mod htlc;
 
 use crate::common::satoshi::Satoshi as Satoshi;
+use crate::common::channelid::ChannelId as ChannelId;
 use crate::common::fee::Fee as Fee;
 use crate::common::fee::FeeRate as FeeRate;
 use crate::common::fee::FeeType as FeeType;
@@ -18,8 +21,93 @@ use channelindirection::ChannelInDirection as ChannelInDirection;
 #[derive(Debug)]
 pub struct Channel {
     capacity: Satoshi,
-    cid: String,
+    cid: ChannelId,
     channel_in_direction: HashMap<Direction, Option<ChannelInDirection>>,
+    // how much each side may send: the balances of the two directions add up to the capacity
+    balances: HashMap<Direction, Satoshi>,
+}
+
+impl Channel {
+    pub fn new(cid: ChannelId, capacity: Satoshi) -> Self {
+        // both directions are disabled until explicitly set
+        // by default, the capacity is split evenly between the two sides
+        let alph_balance = Satoshi(capacity.0 / 2);
+        Channel {
+            capacity,
+            cid,
+            channel_in_direction: HashMap::from([
+                (Direction::Alph, None),
+                (Direction::NonAlph, None),
+            ]),
+            balances: HashMap::from([
+                (Direction::Alph, alph_balance),
+                (Direction::NonAlph, capacity.sub(&alph_balance)),
+            ]),
+        }
+    }
+
+    pub fn get_cid(&self) -> &ChannelId {
+        &self.cid
+    }
+
+    pub fn get_capacity(&self) -> &Satoshi {
+        &self.capacity
+    }
+
+    pub fn set_channel_in_direction(
+        &mut self,
+        direction: Direction,
+        mut ch_in_dir: Option<ChannelInDirection>,
+    ) {
+        if let Some(ch_in_dir) = ch_in_dir.as_mut() {
+            ch_in_dir.set_capacity(self.capacity);
+            ch_in_dir.set_liquidity(self.balances[&direction]);
+        }
+        self.channel_in_direction.insert(direction, ch_in_dir);
+    }
+
+    pub fn get_balance(&self, direction: &Direction) -> &Satoshi {
+        &self.balances[direction]
+    }
+
+    pub fn set_balance(&mut self, direction: Direction, balance: Satoshi) {
+        // the other side gets the rest of the capacity
+        assert!(balance.le(&self.capacity), "Balance {:?} exceeds the capacity {:?}", balance, self.capacity);
+        self.update_balances(direction, balance);
+    }
+
+    pub fn settle(&mut self, direction: Direction, amount: &Satoshi) {
+        // a successful HTLC moves its amount from the sending side to the receiving side
+        let balance = self.balances[&direction];
+        assert!(amount.le(&balance), "Can't settle {:?} with the balance of {:?}", amount, balance);
+        self.update_balances(direction, balance.sub(amount));
+    }
+
+    fn update_balances(&mut self, direction: Direction, balance: Satoshi) {
+        let reverse_balance = self.capacity.sub(&balance);
+        for (direction, balance) in [(direction, balance), (direction.reverse(), reverse_balance)] {
+            self.balances.insert(direction, balance);
+            // the channel direction can't lock more than its side holds
+            if let Some(ch_in_dir) = self.get_channel_in_direction_mut(&direction) {
+                ch_in_dir.set_liquidity(balance);
+            }
+        }
+    }
+
+    pub fn get_channel_in_direction(&self, direction: &Direction) -> Option<&ChannelInDirection> {
+        self.channel_in_direction.get(direction).and_then(|ch_in_dir| ch_in_dir.as_ref())
+    }
+
+    pub fn get_channel_in_direction_mut(
+        &mut self,
+        direction: &Direction,
+    ) -> Option<&mut ChannelInDirection> {
+        self.channel_in_direction.get_mut(direction).and_then(|ch_in_dir| ch_in_dir.as_mut())
+    }
+
+    pub fn is_enabled(&self, direction: &Direction) -> bool {
+        self.get_channel_in_direction(direction).is_some()
+    }
 }
 
 
@@ -39,4 +127,43 @@ mod tests {
         // TODO: continue
         assert!(true);
     }
+
+    #[test]
+    fn channel_enable_direction() {
+        let mut ch = Channel::new(ChannelId(String::from("cid0")), Satoshi(1000));
+        assert!(!ch.is_enabled(&Direction::Alph));
+        assert!(!ch.is_enabled(&Direction::NonAlph));
+        let ch_in_dir = ChannelInDirection::new(
+            None,
+            Some(Box::new(Fee::new(Satoshi(1), FeeRate(0.01)))),
+            None,
+            None,
+            None,
+        );
+        ch.set_channel_in_direction(Direction::Alph, Some(ch_in_dir));
+        assert!(ch.is_enabled(&Direction::Alph));
+        assert!(!ch.is_enabled(&Direction::NonAlph));
+        let ch_in_dir = ch.get_channel_in_direction(&Direction::Alph).unwrap();
+        assert_eq!(ch_in_dir.requires_fee(FeeType::Upfront, &Satoshi(100)), Satoshi(2));
+        assert_eq!(ch.get_cid(), &ChannelId(String::from("cid0")));
+        assert_eq!(ch.get_capacity(), &Satoshi(1000));
+    }
+
+    #[test]
+    fn channel_balances() {
+        let mut ch = Channel::new(ChannelId(String::from("cid0")), Satoshi(1001));
+        assert_eq!(ch.get_balance(&Direction::Alph), &Satoshi(500));
+        assert_eq!(ch.get_balance(&Direction::NonAlph), &Satoshi(501));
+        ch.set_balance(Direction::Alph, Satoshi(800));
+        assert_eq!(ch.get_balance(&Direction::NonAlph), &Satoshi(201));
+        ch.set_channel_in_direction(Direction::Alph, Some(ChannelInDirection::new(None, None, None, None, None)));
+        let available = |ch: &Channel| ch.get_channel_in_direction(&Direction::Alph).unwrap()
+            .get_available_liquidity(&crate::common::timestamp::Timestamp(0));
+        assert_eq!(available(&ch), Some(Satoshi(800)));
+        // settling moves the amount to the other side, and the channel direction can lock less
+        ch.settle(Direction::Alph, &Satoshi(300));
+        assert_eq!(ch.get_balance(&Direction::Alph), &Satoshi(500));
+        assert_eq!(ch.get_balance(&Direction::NonAlph), &Satoshi(501));
+        assert_eq!(available(&ch), Some(Satoshi(500)));
+    }
 }
\ No newline at end of file
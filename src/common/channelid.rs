@@ -1,2 +1,2 @@
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash)]
 pub struct ChannelId(pub String);
\ No newline at end of file
@@ -1,7 +1,139 @@
-#[derive(Debug)]
+// Failure codes as defined in BOLT 4:
+// https://github.com/lightning/bolts/blob/master/04-onion-routing.md#failure-messages
+pub const BADONION: u16 = 0x8000;
+pub const PERM: u16 = 0x4000;
+pub const NODE: u16 = 0x2000;
+pub const UPDATE: u16 = 0x1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ErrorType {
+    // internal rejection reasons: what actually happened at the erring hop
     LowBalance,
     NoSlots,
     LowFee,
     FailedDeliberately,
+    // the circuit breaker of the receiving node turned down an HTLC from this peer
+    RateLimited,
+    // BOLT 4 failure messages: what the erring hop reports on the wire
+    TemporaryChannelFailure,
+    FeeInsufficient,
+    IncorrectCltvExpiry,
+    AmountBelowMinimum,
+    ExpiryTooSoon,
+    UnknownNextPeer,
+    ChannelDisabled,
+    IncorrectOrUnknownPaymentDetails,
+    TemporaryNodeFailure,
+    PermanentNodeFailure,
+}
+
+impl ErrorType {
+    pub fn is_internal(&self) -> bool {
+        matches!(
+            self,
+            ErrorType::LowBalance
+            | ErrorType::NoSlots
+            | ErrorType::LowFee
+            | ErrorType::FailedDeliberately
+            | ErrorType::RateLimited
+        )
+    }
+
+    pub fn to_bolt4(&self) -> ErrorType {
+        // Real nodes don't tell why exactly they have no capacity for an HTLC:
+        // lack of slots, lack of liquidity, and malice all look the same on the wire.
+        match self {
+            ErrorType::LowBalance
+            | ErrorType::NoSlots
+            | ErrorType::FailedDeliberately
+            | ErrorType::RateLimited => ErrorType::TemporaryChannelFailure,
+            ErrorType::LowFee => ErrorType::FeeInsufficient,
+            bolt4_error_type => *bolt4_error_type,
+        }
+    }
+
+    pub fn failure_code(&self) -> u16 {
+        match self.to_bolt4() {
+            ErrorType::TemporaryChannelFailure => UPDATE | 7,
+            ErrorType::AmountBelowMinimum => UPDATE | 11,
+            ErrorType::FeeInsufficient => UPDATE | 12,
+            ErrorType::IncorrectCltvExpiry => UPDATE | 13,
+            ErrorType::ExpiryTooSoon => UPDATE | 14,
+            ErrorType::ChannelDisabled => UPDATE | 20,
+            ErrorType::UnknownNextPeer => PERM | 10,
+            ErrorType::IncorrectOrUnknownPaymentDetails => PERM | 15,
+            ErrorType::TemporaryNodeFailure => NODE | 2,
+            ErrorType::PermanentNodeFailure => PERM | NODE | 2,
+            internal_error_type => panic!("No failure code for {:?}", internal_error_type),
+        }
+    }
+
+    pub fn is_permanent(&self) -> bool {
+        self.failure_code() & PERM != 0
+    }
+
+    pub fn is_node_failure(&self) -> bool {
+        self.failure_code() & NODE != 0
+    }
+
+    pub fn is_liquidity_failure(&self) -> bool {
+        // as far as the sender can tell, the erring hop lacked the liquidity (or slots) to forward the HTLC
+        self.to_bolt4() == ErrorType::TemporaryChannelFailure
+    }
+
+    pub fn has_channel_update(&self) -> bool {
+        // the erring hop attaches its latest channel update (e.g., new fees)
+        self.failure_code() & UPDATE != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn errortype_internal_to_bolt4() {
+        assert_eq!(ErrorType::NoSlots.to_bolt4(), ErrorType::TemporaryChannelFailure);
+        assert_eq!(ErrorType::LowBalance.to_bolt4(), ErrorType::TemporaryChannelFailure);
+        assert_eq!(ErrorType::FailedDeliberately.to_bolt4(), ErrorType::TemporaryChannelFailure);
+        assert_eq!(ErrorType::RateLimited.to_bolt4(), ErrorType::TemporaryChannelFailure);
+        assert_eq!(ErrorType::LowFee.to_bolt4(), ErrorType::FeeInsufficient);
+        assert_eq!(ErrorType::ExpiryTooSoon.to_bolt4(), ErrorType::ExpiryTooSoon);
+        assert!(ErrorType::NoSlots.is_internal());
+        assert!(!ErrorType::ChannelDisabled.is_internal());
+    }
+
+    #[test]
+    fn errortype_failure_codes() {
+        assert_eq!(ErrorType::TemporaryChannelFailure.failure_code(), 0x1007);
+        assert_eq!(ErrorType::FeeInsufficient.failure_code(), 0x100c);
+        assert_eq!(ErrorType::ChannelDisabled.failure_code(), 0x1014);
+        assert_eq!(ErrorType::UnknownNextPeer.failure_code(), 0x400a);
+        assert_eq!(ErrorType::IncorrectOrUnknownPaymentDetails.failure_code(), 0x400f);
+        assert_eq!(ErrorType::NoSlots.failure_code(), 0x1007);
+    }
+
+    #[test]
+    fn errortype_liquidity_failures() {
+        assert!(ErrorType::LowBalance.is_liquidity_failure());
+        assert!(ErrorType::NoSlots.is_liquidity_failure());
+        assert!(ErrorType::TemporaryChannelFailure.is_liquidity_failure());
+        assert!(!ErrorType::LowFee.is_liquidity_failure());
+        assert!(!ErrorType::ChannelDisabled.is_liquidity_failure());
+        assert!(!ErrorType::ExpiryTooSoon.is_liquidity_failure());
+        assert!(!ErrorType::TemporaryNodeFailure.is_liquidity_failure());
+        assert!(!ErrorType::IncorrectOrUnknownPaymentDetails.is_liquidity_failure());
+    }
+
+    #[test]
+    fn errortype_flags() {
+        assert!(ErrorType::UnknownNextPeer.is_permanent());
+        assert!(!ErrorType::UnknownNextPeer.has_channel_update());
+        assert!(!ErrorType::LowFee.is_permanent());
+        assert!(ErrorType::LowFee.has_channel_update());
+        assert!(!ErrorType::ChannelDisabled.is_node_failure());
+        assert!(ErrorType::TemporaryNodeFailure.is_node_failure());
+        assert!(!ErrorType::TemporaryNodeFailure.is_permanent());
+        assert!(ErrorType::PermanentNodeFailure.is_permanent());
+    }
 }
\ No newline at end of file
@@ -1,9 +1,13 @@
 use crate::common::satoshi::Satoshi as Satoshi;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum FeeType {
     Upfront,
     Success,
+    // unconditional, charged for the time an HTLC is held
+    Hold,
+    // paid by the downstream node to the upstream node for the time it holds an HTLC
+    ReverseHold,
 }
 
 #[derive(Debug, Clone)]
@@ -21,19 +25,18 @@ impl Default for Fee {
 }
 impl Fee {
     pub fn apply(&self, amount: &Satoshi) -> Satoshi {
-        amount.mul(self.fee_rate.0).add(&self.base_fee)
+        if self.fee_rate.0 >= 0.0 {
+            return amount.mul(self.fee_rate.0).add(&self.base_fee);
+        }
+        // with a negative rate, the fee is floored at zero
+        let fee = (amount.0 as f64 * self.fee_rate.0) as i64 + self.base_fee.0 as i64;
+        Satoshi(std::cmp::max(fee, 0) as u64)
     }
     pub fn new(base_fee: Satoshi, fee_rate: FeeRate) -> Self {
         // Can a fee rate be negative?
         // Well, it can certainly be zero.
-        // We haven't thought much about implications of negative fees,
-        // although that could be an interesting research question.
-        // To ensure correctness w.r.t. existing code,
-        // we could have used the following assert here,
-        // but does equality comparison work with floats?..
-        // Let's leave it at that: rates may be negative,
-        // but negative rates are not (yet) used in simulations.
-        //assert!(fee_rate.0 >= 0.0);
+        // Negative rates are allowed (e.g., as discounts, see InboundFee),
+        // but the resulting fee never goes below zero.
         Fee {
             base_fee: base_fee,
             fee_rate: fee_rate,
@@ -50,5 +53,16 @@ mod tests {
         let amount = Satoshi(100);
         let fee = Fee::new(Satoshi(2), FeeRate(0.01));
         assert_eq!(fee.apply(&amount), Satoshi(3));
+        // large amounts are not capped by the signed arithmetic of negative rates
+        let fee = Fee::new(Satoshi(0), FeeRate(1.0));
+        assert_eq!(fee.apply(&Satoshi(u64::MAX / 2 + 1)), Satoshi(u64::MAX / 2 + 1));
+    }
+
+    #[test]
+    fn fee_negative_rate() {
+        let fee = Fee::new(Satoshi(5), FeeRate(-0.01));
+        assert_eq!(fee.apply(&Satoshi(100)), Satoshi(4));
+        // the fee is floored at zero
+        assert_eq!(fee.apply(&Satoshi(1000)), Satoshi(0));
     }
 }
\ No newline at end of file
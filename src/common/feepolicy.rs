@@ -0,0 +1,240 @@
+use std::fmt::Debug;
+
+use crate::common::duration::Duration as Duration;
+use crate::common::fee::{Fee as Fee, FeeRate as FeeRate};
+use crate::common::holdfee::HoldFee as HoldFee;
+use crate::common::satoshi::Satoshi as Satoshi;
+
+// A fee scheme set by a channel direction.
+// Both upfront and success fees are fee policies:
+// new research fee schemes only need to implement this trait.
+pub trait FeePolicy: Debug {
+    fn get_fee(&self, context: &FeeContext) -> Satoshi;
+
+    // the hold fee this policy already charges up front, if any
+    fn get_prepaid_hold_fee(&self) -> Option<&HoldFee> {
+        None
+    }
+}
+
+// Everything a fee policy may depend on when pricing a hop.
+#[derive(Debug, Clone)]
+pub struct FeeContext {
+    pub amount: Satoshi,
+    // the success fee of the same hop (only meaningful for upfront fees)
+    pub success_fee: Satoshi,
+    // how long the HTLC is expected to be held
+    pub hold_time: Duration,
+    pub occupancy: Occupancy,
+}
+
+// How loaded a channel direction is, as shares in [0, 1].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Occupancy {
+    pub slots: f64,
+    pub liquidity: f64,
+}
+
+impl FeeContext {
+    pub fn new(amount: Satoshi) -> Self {
+        FeeContext {
+            amount,
+            success_fee: Satoshi(0),
+            hold_time: Duration(0),
+            occupancy: Occupancy::default(),
+        }
+    }
+}
+
+// The rate-based fee: base fee plus a share of the amount.
+impl FeePolicy for Fee {
+    fn get_fee(&self, context: &FeeContext) -> Satoshi {
+        self.apply(&context.amount)
+    }
+}
+
+// The same fee for any HTLC.
+#[derive(Debug, Clone)]
+pub struct FixedFee(pub Satoshi);
+
+impl FeePolicy for FixedFee {
+    fn get_fee(&self, _context: &FeeContext) -> Satoshi {
+        self.0
+    }
+}
+
+// A share of the success fee of the same hop, as in the original unjamming proposal.
+#[derive(Debug, Clone)]
+pub struct SuccessProportionalFee(pub f64);
+
+impl FeePolicy for SuccessProportionalFee {
+    fn get_fee(&self, context: &FeeContext) -> Satoshi {
+        context.success_fee.mul(self.0)
+    }
+}
+
+// How much a congestion-priced fee grows with occupancy:
+// each curve gives a multiplier of 1 for an empty channel direction.
+#[derive(Debug, Clone)]
+pub enum PricingCurve {
+    // 1 + slope * occupancy
+    Linear(f64),
+    // e ^ (exponent * occupancy)
+    Exponential(f64),
+    // 1 below the threshold, the multiplier from the threshold on
+    Step { threshold: f64, multiplier: f64 },
+}
+
+impl PricingCurve {
+    pub fn get_multiplier(&self, occupancy: f64) -> f64 {
+        match self {
+            PricingCurve::Linear(slope) => 1.0 + slope * occupancy,
+            PricingCurve::Exponential(exponent) => (exponent * occupancy).exp(),
+            PricingCurve::Step { threshold, multiplier } => {
+                if occupancy >= *threshold { *multiplier } else { 1.0 }
+            }
+        }
+    }
+}
+
+// The base fee scaled up with the occupancy of slots or liquidity, whichever is higher.
+#[derive(Debug, Clone)]
+pub struct CongestionFee {
+    pub base: Fee,
+    pub curve: PricingCurve,
+}
+
+impl FeePolicy for CongestionFee {
+    fn get_fee(&self, context: &FeeContext) -> Satoshi {
+        let occupancy = context.occupancy.slots.max(context.occupancy.liquidity);
+        self.base.apply(&context.amount).mul(self.curve.get_multiplier(occupancy))
+    }
+}
+
+// A hold fee charged up front, estimated from the expected hold time instead of settled at resolution.
+// A channel direction that prepays its hold fee this way is not charged it again at resolution.
+#[derive(Debug, Clone)]
+pub struct HoldTimeFee(pub HoldFee);
+
+impl FeePolicy for HoldTimeFee {
+    fn get_fee(&self, context: &FeeContext) -> Satoshi {
+        self.0.apply(&context.amount, &context.hold_time)
+    }
+
+    fn get_prepaid_hold_fee(&self) -> Option<&HoldFee> {
+        Some(&self.0)
+    }
+}
+
+// A discount (if negative) or surcharge (if positive) a node applies to HTLCs
+// arriving via one of its channels, on top of the fee of the outgoing channel (or of the last hop, for the receiver),
+// as inbound fees in lnd 0.18.
+#[derive(Debug, Clone)]
+pub struct InboundFee {
+    pub base_fee: i64,
+    pub fee_rate: FeeRate,
+}
+
+impl InboundFee {
+    pub fn new(base_fee: i64, fee_rate: FeeRate) -> Self {
+        InboundFee {
+            base_fee,
+            fee_rate,
+        }
+    }
+
+    pub fn apply(&self, amount: &Satoshi) -> i64 {
+        (amount.0 as f64 * self.fee_rate.0) as i64 + self.base_fee
+    }
+}
+
+// An outgoing fee policy adjusted by the inbound fee of the incoming channel, if any.
+// The total fee is floored at zero.
+#[derive(Debug)]
+pub struct WithInboundFee<'a> {
+    pub outbound: &'a dyn FeePolicy,
+    pub inbound: Option<&'a InboundFee>,
+}
+
+impl FeePolicy for WithInboundFee<'_> {
+    fn get_fee(&self, context: &FeeContext) -> Satoshi {
+        let outbound_fee = self.outbound.get_fee(context);
+        match self.inbound {
+            Some(inbound) => {
+                let fee = outbound_fee.0 as i64 + inbound.apply(&context.amount);
+                Satoshi(std::cmp::max(fee, 0) as u64)
+            }
+            None => outbound_fee,
+        }
+    }
+
+    fn get_prepaid_hold_fee(&self) -> Option<&HoldFee> {
+        self.outbound.get_prepaid_hold_fee()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> FeeContext {
+        FeeContext {
+            amount: Satoshi(1000),
+            success_fee: Satoshi(50),
+            hold_time: Duration(10),
+            occupancy: Occupancy { slots: 0.5, liquidity: 0.1 },
+        }
+    }
+
+    #[test]
+    fn feepolicy_get_fee() {
+        let policies: Vec<(Box<dyn FeePolicy>, Satoshi)> = vec![
+            (Box::new(Fee::new(Satoshi(2), FeeRate(0.01))), Satoshi(12)),
+            (Box::new(FixedFee(Satoshi(7))), Satoshi(7)),
+            (Box::new(SuccessProportionalFee(0.1)), Satoshi(5)),
+            (
+                Box::new(CongestionFee { base: Fee::new(Satoshi(2), FeeRate(0.01)), curve: PricingCurve::Linear(2.0) }),
+                Satoshi(24),
+            ),
+            // 1% of the amount per time unit after 5 time units
+            (Box::new(HoldTimeFee(HoldFee::new(10_000, Duration(5), Satoshi(100)))), Satoshi(50)),
+        ];
+        for (policy, expected_fee) in policies {
+            assert_eq!(policy.get_fee(&context()), expected_fee, "{:?}", policy);
+        }
+    }
+
+    #[test]
+    fn feepolicy_congestion_fee_grows_with_occupancy() {
+        let policy = CongestionFee { base: Fee::new(Satoshi(10), FeeRate(0.0)), curve: PricingCurve::Linear(4.0) };
+        let fee = |slots, liquidity| policy.get_fee(&FeeContext { occupancy: Occupancy { slots, liquidity }, ..context() });
+        assert_eq!(fee(0.0, 0.0), Satoshi(10));
+        assert_eq!(fee(0.25, 0.0), Satoshi(20));
+        // liquidity in flight counts as well
+        assert_eq!(fee(0.0, 0.25), Satoshi(20));
+        assert_eq!(fee(1.0, 0.5), Satoshi(50));
+    }
+
+    #[test]
+    fn feepolicy_inbound_fee() {
+        let outbound = Fee::new(Satoshi(2), FeeRate(0.01));
+        let discount = InboundFee::new(-1, FeeRate(-0.005));
+        assert_eq!(WithInboundFee { outbound: &outbound, inbound: Some(&discount) }.get_fee(&context()), Satoshi(6));
+        let surcharge = InboundFee::new(3, FeeRate(0.0));
+        assert_eq!(WithInboundFee { outbound: &outbound, inbound: Some(&surcharge) }.get_fee(&context()), Satoshi(15));
+        // the discount can't make the total fee negative
+        let large_discount = InboundFee::new(-100, FeeRate(0.0));
+        assert_eq!(WithInboundFee { outbound: &outbound, inbound: Some(&large_discount) }.get_fee(&context()), Satoshi(0));
+    }
+
+    #[test]
+    fn feepolicy_pricing_curves() {
+        let exponential = PricingCurve::Exponential(2.0_f64.ln());
+        assert_eq!(exponential.get_multiplier(0.0), 1.0);
+        assert!((exponential.get_multiplier(1.0) - 2.0).abs() < 1e-9);
+        let step = PricingCurve::Step { threshold: 0.8, multiplier: 10.0 };
+        assert_eq!(step.get_multiplier(0.79), 1.0);
+        assert_eq!(step.get_multiplier(0.8), 10.0);
+        assert_eq!(PricingCurve::Linear(1.0).get_multiplier(0.5), 1.5);
+    }
+}
@@ -0,0 +1,49 @@
+use crate::common::duration::Duration as Duration;
+use crate::common::satoshi::Satoshi as Satoshi;
+
+// An unconditional fee for the time an HTLC occupies a slot.
+// Unlike upfront fees, it is only known once the HTLC resolves,
+// so it is settled at resolution, whether the payment succeeds or fails
+// (feepolicy::HoldTimeFee charges it up front instead, from the expected hold time).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct HoldFee {
+    // charged on the amount for every time unit beyond the grace period, in parts per million
+    pub rate_ppm: u64,
+    // honest payments resolving within the grace period pay nothing
+    pub grace_period: Duration,
+    // the fee never exceeds the cap, however long the HTLC is held
+    pub cap: Satoshi,
+}
+
+impl HoldFee {
+    pub fn new(rate_ppm: u64, grace_period: Duration, cap: Satoshi) -> Self {
+        HoldFee {
+            rate_ppm,
+            grace_period,
+            cap,
+        }
+    }
+
+    pub fn apply(&self, amount: &Satoshi, hold_time: &Duration) -> Satoshi {
+        let charged_time = hold_time.0.saturating_sub(self.grace_period.0);
+        // u128 to avoid overflows for large amounts held for long
+        let fee = amount.0 as u128 * self.rate_ppm as u128 * charged_time as u128 / 1_000_000;
+        Satoshi(std::cmp::min(fee, self.cap.0 as u128) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn holdfee_grace_period_and_cap() {
+        // 1% of the amount per time unit after 10 time units, at most 500
+        let hold_fee = HoldFee::new(10_000, Duration(10), Satoshi(500));
+        let amount = Satoshi(1000);
+        assert_eq!(hold_fee.apply(&amount, &Duration(0)), Satoshi(0));
+        assert_eq!(hold_fee.apply(&amount, &Duration(10)), Satoshi(0));
+        assert_eq!(hold_fee.apply(&amount, &Duration(15)), Satoshi(50));
+        assert_eq!(hold_fee.apply(&amount, &Duration(1000)), Satoshi(500));
+    }
+}
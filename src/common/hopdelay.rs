@@ -0,0 +1,41 @@
+use rand::Rng;
+
+use crate::common::duration::Duration as Duration;
+
+// The latency each hop adds when it forwards an HTLC, and again when it passes on the fulfil or fail.
+// Each delay is drawn uniformly between the bounds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HopDelay {
+    pub min_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl HopDelay {
+    pub fn new(min_delay: Duration, max_delay: Duration) -> Self {
+        assert!(min_delay <= max_delay,
+        "Minimal hop delay {:?} exceeds maximal hop delay {:?}", min_delay, max_delay);
+        HopDelay { min_delay, max_delay }
+    }
+
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> Duration {
+        Duration(rng.gen_range(self.min_delay.0..=self.max_delay.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn hopdelay_sample() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let hop_delay = HopDelay::new(Duration(1), Duration(3));
+        let delays: Vec<Duration> = (0..100).map(|_| hop_delay.sample(&mut rng)).collect();
+        for delay in 1..=3 {
+            assert!(delays.contains(&Duration(delay)));
+        }
+        assert!(delays.iter().all(|delay| delay >= &Duration(1) && delay <= &Duration(3)));
+        assert_eq!(HopDelay::new(Duration(2), Duration(2)).sample(&mut rng), Duration(2));
+    }
+}
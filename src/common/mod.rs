@@ -1,4 +1,7 @@
 pub mod fee;
+pub mod feepolicy;
+pub mod holdfee;
+pub mod hopdelay;
 pub mod satoshi;
 pub mod nodeid;
 pub mod channelid;
@@ -8,4 +11,8 @@ pub mod timestamp;
 pub mod duration;
 pub mod params;
 pub mod errortype;
-pub mod scheduletype;
\ No newline at end of file
+pub mod scheduletype;
+pub mod trafficclass;
+pub mod paymentfailure;
+pub mod upfrontfeepolicy;
+pub mod revenue;
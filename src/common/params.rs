@@ -10,3 +10,7 @@ pub const MAX_ROUTE_LENGTH: u16 = 20;
 // FeeParams
 pub const SUCCESS_BASE_FEE: u64 = 1;
 pub const SUCCESS_FEE_RATE: f64 = 5.0 / (1000 * 1000) as f64;
+
+// SimulationParams
+// timestamps and durations are in seconds
+pub const TIME_UNITS_PER_HOUR: u64 = 60 * 60;
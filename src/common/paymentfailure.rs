@@ -0,0 +1,13 @@
+use crate::common::channelid::ChannelId;
+use crate::common::errortype::ErrorType;
+
+// What the sender learns when a payment fails.
+// The error type is whatever the erring hop reported (it may be spoofed).
+// The erring hop is indexed from the sender:
+// hop 0 is the channel from the sender to the first intermediary.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentFailure {
+    pub error_type: ErrorType,
+    pub erring_hop: usize,
+    pub erring_channel: ChannelId,
+}
@@ -1,13 +1,17 @@
-// use rand::distributions::{Alphanumeric, DistString};
+use rand::distributions::{Alphanumeric, DistString};
 
-// #[derive(Debug, PartialEq, Eq, Hash)]
-// pub struct PaymentId(pub String);
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash)]
+pub struct PaymentId(pub String);
 
-// // TODO implement random ID generation as a trait
-// // that PaymentId, ChannelId, and NodeId derive
-// impl PaymentId {
-//     pub fn new() -> Self {
-//         use rand::distributions::{Alphanumeric, DistString};
-//         PaymentId(Alphanumeric.sample_string(&mut rand::thread_rng(), 16))
-//     }
-// }
\ No newline at end of file
+// TODO implement random ID generation as a trait
+// that PaymentId, ChannelId, and NodeId derive
+impl PaymentId {
+    pub fn new() -> Self {
+        PaymentId(Alphanumeric.sample_string(&mut rand::thread_rng(), 16))
+    }
+}
+impl Default for PaymentId {
+    fn default() -> Self {
+        Self::new()
+    }
+}
\ No newline at end of file
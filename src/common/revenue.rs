@@ -0,0 +1,64 @@
+use crate::common::fee::FeeType;
+use crate::common::satoshi::Satoshi;
+
+// Revenue may be negative (e.g., for senders who only pay fees),
+// hence signed integers instead of Satoshi.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Revenue {
+    pub upfront: i64,
+    pub success: i64,
+    pub hold: i64,
+    pub reverse_hold: i64,
+}
+
+impl Revenue {
+    pub fn earn(&mut self, fee_type: FeeType, amount: &Satoshi) {
+        match fee_type {
+            FeeType::Upfront => self.upfront += amount.0 as i64,
+            FeeType::Success => self.success += amount.0 as i64,
+            FeeType::Hold => self.hold += amount.0 as i64,
+            FeeType::ReverseHold => self.reverse_hold += amount.0 as i64,
+        }
+    }
+    pub fn spend(&mut self, fee_type: FeeType, amount: &Satoshi) {
+        match fee_type {
+            FeeType::Upfront => self.upfront -= amount.0 as i64,
+            FeeType::Success => self.success -= amount.0 as i64,
+            FeeType::Hold => self.hold -= amount.0 as i64,
+            FeeType::ReverseHold => self.reverse_hold -= amount.0 as i64,
+        }
+    }
+    pub fn total(&self) -> i64 {
+        self.upfront + self.success + self.hold + self.reverse_hold
+    }
+    pub fn add(&self, other: &Revenue) -> Revenue {
+        Revenue {
+            upfront: self.upfront + other.upfront,
+            success: self.success + other.success,
+            hold: self.hold + other.hold,
+            reverse_hold: self.reverse_hold + other.reverse_hold,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn revenue_earn_and_spend() {
+        let mut revenue = Revenue::default();
+        revenue.earn(FeeType::Upfront, &Satoshi(5));
+        revenue.spend(FeeType::Success, &Satoshi(7));
+        revenue.earn(FeeType::Hold, &Satoshi(3));
+        revenue.spend(FeeType::ReverseHold, &Satoshi(1));
+        assert_eq!(revenue.upfront, 5);
+        assert_eq!(revenue.success, -7);
+        assert_eq!(revenue.hold, 3);
+        assert_eq!(revenue.reverse_hold, -1);
+        assert_eq!(revenue.total(), 0);
+        let doubled = revenue.add(&revenue);
+        assert_eq!(doubled.upfront, 10);
+        assert_eq!(doubled.success, -14);
+    }
+}
\ No newline at end of file
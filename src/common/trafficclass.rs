@@ -0,0 +1,61 @@
+use rand::Rng;
+
+use crate::common::duration::Duration as Duration;
+
+// Honest traffic comes in classes, so that statistics can tell them apart.
+// Hold invoices, swaps and async payments legitimately hold HTLCs for minutes to hours:
+// mitigations that punish long holds hit them too.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TrafficClass {
+    #[default]
+    Regular,
+    HoldInvoice,
+}
+
+// Hold times spread over orders of magnitude (e.g., a minute to a few hours),
+// so we draw them log-uniformly between the bounds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HoldTimeDistribution {
+    pub min_hold_time: Duration,
+    pub max_hold_time: Duration,
+}
+
+impl HoldTimeDistribution {
+    pub fn new(min_hold_time: Duration, max_hold_time: Duration) -> Self {
+        assert!(min_hold_time.0 > 0, "Minimal hold time must be positive");
+        assert!(min_hold_time <= max_hold_time,
+        "Minimal hold time {:?} exceeds maximal hold time {:?}", min_hold_time, max_hold_time);
+        HoldTimeDistribution { min_hold_time, max_hold_time }
+    }
+
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> Duration {
+        let min = (self.min_hold_time.0 as f64).ln();
+        let max = (self.max_hold_time.0 as f64).ln();
+        let hold_time = rng.gen_range(min..=max).exp().round() as u64;
+        // rounding must not take us out of bounds
+        Duration(hold_time.clamp(self.min_hold_time.0, self.max_hold_time.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn holdtimedistribution_sample() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let distribution = HoldTimeDistribution::new(Duration(60), Duration(3 * 60 * 60));
+        let hold_times: Vec<Duration> = (0..1000).map(|_| distribution.sample(&mut rng)).collect();
+        assert!(hold_times.iter().all(|hold_time| hold_time >= &Duration(60) && hold_time <= &Duration(3 * 60 * 60)));
+        // log-uniform: about half of the hold times are below the geometric mean of the bounds (~10 minutes)
+        let num_short = hold_times.iter().filter(|hold_time| hold_time.0 < 588).count();
+        assert!((400..600).contains(&num_short));
+    }
+
+    #[test]
+    #[should_panic]
+    fn holdtimedistribution_invalid_bounds() {
+        let _distribution = HoldTimeDistribution::new(Duration(60), Duration(30));
+    }
+}
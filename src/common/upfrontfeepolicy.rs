@@ -0,0 +1,56 @@
+use crate::common::duration::Duration as Duration;
+
+// What happens to upfront fees when a payment fails or resolves.
+// Nodes upstream of the erring hop always keep their upfront fees:
+// the HTLC did occupy their slots, that's what upfront fees are for.
+// The only question is whether the erring node is paid for failing.
+// Alternatively, upfront fees are escrowed and (partly) refunded if the HTLC resolves fast,
+// so that honest payments pay almost nothing while slow jams pay in full.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpfrontFeePolicy {
+    KeepAll,
+    RefundErringHop,
+    // full refund up to full_refund_time, linearly less up to no_refund_time, none after that
+    RefundFastResolution {
+        full_refund_time: Duration,
+        no_refund_time: Duration,
+    },
+}
+
+impl UpfrontFeePolicy {
+    pub fn get_refund_share(&self, hold_time: &Duration) -> f64 {
+        match self {
+            UpfrontFeePolicy::KeepAll | UpfrontFeePolicy::RefundErringHop => 0.0,
+            UpfrontFeePolicy::RefundFastResolution { full_refund_time, no_refund_time } => {
+                assert!(full_refund_time <= no_refund_time,
+                "Full refund time {:?} must not exceed no refund time {:?}", full_refund_time, no_refund_time);
+                if hold_time <= full_refund_time {
+                    1.0
+                } else if hold_time >= no_refund_time {
+                    0.0
+                } else {
+                    (no_refund_time.0 - hold_time.0) as f64 / (no_refund_time.0 - full_refund_time.0) as f64
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upfrontfeepolicy_refund_share() {
+        let policy = UpfrontFeePolicy::RefundFastResolution {
+            full_refund_time: Duration(10),
+            no_refund_time: Duration(30),
+        };
+        assert_eq!(policy.get_refund_share(&Duration(0)), 1.0);
+        assert_eq!(policy.get_refund_share(&Duration(10)), 1.0);
+        assert_eq!(policy.get_refund_share(&Duration(15)), 0.75);
+        assert_eq!(policy.get_refund_share(&Duration(30)), 0.0);
+        assert_eq!(policy.get_refund_share(&Duration(100)), 0.0);
+        assert_eq!(UpfrontFeePolicy::KeepAll.get_refund_share(&Duration(0)), 0.0);
+    }
+}
@@ -5,10 +5,19 @@ use crate::common::timestamp::Timestamp;
 use crate::common::satoshi::Satoshi;
 use crate::common::fee::{Fee, FeeRate};
 
+pub mod attacker;
 pub mod channel;
 pub mod common;
+pub mod network;
+pub mod node;
 pub mod payment;
+pub mod reputation;
+pub mod router;
 pub mod schedule;
+pub mod sender;
+pub mod simulator;
+#[cfg(test)]
+mod testutils;
 
 const SUCCESS_PROBABILITY: f64 = 1.0;
 const SIMULATION_END_TIME: Timestamp = Timestamp(100);
@@ -0,0 +1,78 @@
+use crate::channel::htlc::Htlc as Htlc;
+use crate::common::{
+    channelid::ChannelId,
+    errortype::ErrorType,
+    fee::FeeType,
+    nodeid::NodeId,
+    paymentfailure::PaymentFailure,
+    paymentid::PaymentId,
+    paymentresult::PaymentResult,
+    satoshi::Satoshi,
+    timestamp::Timestamp,
+    upfrontfeepolicy::UpfrontFeePolicy,
+};
+
+use super::Network as Network;
+
+impl Network {
+    pub fn fail_payment_at(&mut self, payment_id: &PaymentId, route: &[NodeId], resolution_time: &Timestamp) {
+        // The receiver will fail a payment that has already reached it (e.g., an incomplete MPP part) at the given time.
+        // The failure travels back hop by hop: each HTLC stays in flight until it reaches its hop,
+        // and is resolved without success fees.
+        // HTLCs that have already been resolved (released from the slots) are left as is.
+        for nodes in route.windows(2) {
+            let (upstream, downstream) = (&nodes[0], &nodes[1]);
+            // the sender may have picked any of the channels between the nodes
+            let cids: Vec<ChannelId> = self.get_cids_in_direction(upstream, downstream).cloned().collect();
+            for cid in cids {
+                let ch_in_dir = self.get_channel_in_direction_mut(&cid, upstream, downstream);
+                for (mut htlc, _) in ch_in_dir.remove_htlcs_of_payment(payment_id) {
+                    htlc.desired_result = PaymentResult::FAILURE;
+                    ch_in_dir.push_htlc(resolution_time.add(&htlc.resolution_delay), htlc);
+                }
+            }
+        }
+    }
+
+    pub(super) fn fail_payment(
+        &mut self,
+        route: &[NodeId],
+        forwarded: &[(ChannelId, Htlc, Satoshi)],
+        error_type: ErrorType,
+        erring_hop: usize,
+        erring_channel: ChannelId,
+        failure_times: &[Timestamp],
+    ) -> PaymentFailure {
+        *self.num_failures.entry((erring_channel.clone(), error_type)).or_insert(0) += 1;
+        self.fail_upstream(route, forwarded, failure_times);
+        PaymentFailure {
+            error_type,
+            erring_hop,
+            erring_channel,
+        }
+    }
+
+    pub(super) fn fail_upstream(
+        &mut self,
+        route: &[NodeId],
+        forwarded: &[(ChannelId, Htlc, Satoshi)],
+        failure_times: &[Timestamp],
+    ) {
+        // The failure travels back to the sender, hop by hop:
+        // the HTLC stored at each upstream hop stays in flight until the failure reaches it,
+        // and is then resolved without a success fee, like any other failed HTLC.
+        // Upstream nodes keep their upfront fees: their slots have been occupied.
+        let erring_hop = forwarded.len();
+        if erring_hop > 0 && self.upfront_fee_policy == UpfrontFeePolicy::RefundErringHop {
+            let (_, htlc, upfront_fee) = &forwarded[erring_hop - 1];
+            self.refund_fee(FeeType::Upfront, &route[erring_hop], &route[erring_hop - 1], upfront_fee, htlc);
+        }
+        for (hop, (cid, htlc, _)) in forwarded.iter().enumerate() {
+            let ch_in_dir = self.get_channel_in_direction_mut(cid, &route[hop], &route[hop + 1]);
+            let (mut stored_htlc, _) = ch_in_dir.remove_htlc(htlc)
+                .unwrap_or_else(|| panic!("HTLC {:?} not found in channel {:?}", htlc, cid));
+            stored_htlc.desired_result = PaymentResult::FAILURE;
+            ch_in_dir.push_htlc(failure_times[hop].clone(), stored_htlc);
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use crate::channel::htlc::Htlc as Htlc;
+use crate::common::{fee::FeeType, nodeid::NodeId, satoshi::Satoshi};
+
+use super::Network as Network;
+
+impl Network {
+    pub(super) fn transfer_fee(&mut self, fee_type: FeeType, payer: &NodeId, payee: &NodeId, amount: &Satoshi, htlc: &Htlc) {
+        self.move_fee(fee_type, payer, payee, amount);
+        // fees are cumulative, so the sender pays (or, for reverse hold fees, gets paid) those of all hops on the first one
+        if htlc.hop == 0 {
+            let class_fees = self.class_fees.entry(htlc.traffic_class).or_default();
+            match fee_type {
+                FeeType::ReverseHold => class_fees.spend(fee_type, amount),
+                _ => class_fees.earn(fee_type, amount),
+            }
+        }
+    }
+
+    pub(super) fn refund_fee(&mut self, fee_type: FeeType, payer: &NodeId, payee: &NodeId, amount: &Satoshi, htlc: &Htlc) {
+        self.move_fee(fee_type, payer, payee, amount);
+        if htlc.hop == 0 {
+            self.class_fees.entry(htlc.traffic_class).or_default().spend(fee_type, amount);
+        }
+    }
+
+    fn move_fee(&mut self, fee_type: FeeType, payer: &NodeId, payee: &NodeId, amount: &Satoshi) {
+        self.get_node_mut(payer).get_revenue_mut().spend(fee_type, amount);
+        self.get_node_mut(payee).get_revenue_mut().earn(fee_type, amount);
+        let flow = self.fee_flows.entry((payer.clone(), payee.clone(), fee_type)).or_insert(Satoshi(0));
+        *flow = flow.add(amount);
+    }
+}
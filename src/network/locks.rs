@@ -0,0 +1,67 @@
+use crate::channel::direction::Direction as Direction;
+use crate::channel::htlc::{Htlc as Htlc, HtlcLock as HtlcLock};
+use crate::common::{
+    duration::Duration,
+    nodeid::NodeId,
+    paymentid::PaymentId,
+    timestamp::Timestamp,
+};
+
+use super::Network as Network;
+
+impl Network {
+    pub fn watch_payment(&mut self, payment_id: &PaymentId) {
+        // from now on, keep track of how long the payment's HTLCs lock their channel directions
+        self.watched_payments.entry(payment_id.clone()).or_default();
+    }
+
+    pub fn get_htlc_locks(&self, payment_id: &PaymentId) -> Vec<HtlcLock> {
+        // the locks of the watched payment's resolved HTLCs, and of those still in flight
+        let mut locks = self.watched_payments.get(payment_id).cloned().unwrap_or_default();
+        for (cid, (node_a, node_b)) in &self.channel_ends {
+            for (from, to) in [(node_a, node_b), (node_b, node_a)] {
+                let Some(ch_in_dir) = self.get_channel_in_direction_of(cid, from, to) else {
+                    continue;
+                };
+                locks.extend(ch_in_dir.get_htlcs()
+                    .filter(|(htlc, _)| &htlc.payment_id == payment_id)
+                    .map(|(htlc, resolution_time)| Self::get_htlc_lock(from, to, htlc, resolution_time)));
+            }
+        }
+        locks.sort_by_key(|lock| lock.hop);
+        locks
+    }
+
+    pub fn get_capital_in_flight(&self, node: &NodeId) -> u64 {
+        // the amount times the time locked in the node's outgoing HTLCs that have not been resolved yet
+        self.get_neighbors(node)
+            .flat_map(|peer| self.get_cids(node, peer).into_iter().flatten().map(move |cid| (cid, peer)))
+            .filter_map(|(cid, peer)| self.channels[cid].get_channel_in_direction(&Direction::new(node, peer))
+                .map(|ch_in_dir| (ch_in_dir, peer)))
+            .flat_map(|(ch_in_dir, peer)| ch_in_dir.get_htlcs()
+                .map(move |(htlc, resolution_time)| Self::get_htlc_lock(node, peer, htlc, resolution_time)))
+            .map(|lock| lock.get_amount_time())
+            .sum()
+    }
+
+    pub(super) fn release_htlc_lock(&mut self, upstream: &NodeId, downstream: &NodeId, htlc: &Htlc, resolution_time: &Timestamp) {
+        // the upstream node's capital has been locked in the HTLC until now
+        let lock = Self::get_htlc_lock(upstream, downstream, htlc, resolution_time);
+        let hold_time = Duration(lock.resolved_at.0.saturating_sub(lock.added_at.0));
+        self.get_node_mut(upstream).lock_capital(&htlc.amount, &hold_time);
+        if let Some(locks) = self.watched_payments.get_mut(&htlc.payment_id) {
+            locks.push(lock);
+        }
+    }
+
+    fn get_htlc_lock(upstream: &NodeId, downstream: &NodeId, htlc: &Htlc, resolution_time: &Timestamp) -> HtlcLock {
+        HtlcLock {
+            hop: htlc.hop,
+            from: upstream.clone(),
+            to: downstream.clone(),
+            amount: htlc.amount,
+            added_at: htlc.added_at.clone(),
+            resolved_at: resolution_time.clone(),
+        }
+    }
+}
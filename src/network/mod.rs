@@ -0,0 +1,1474 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use rand::Rng;
+
+use crate::channel::Channel as Channel;
+use crate::channel::bucket::{Bucket as Bucket, BucketOccupancy as BucketOccupancy, BucketParams as BucketParams};
+use crate::channel::channelindirection::ChannelInDirection as ChannelInDirection;
+use crate::channel::circuitbreaker::CircuitBreakerParams as CircuitBreakerParams;
+use crate::channel::direction::Direction as Direction;
+use crate::channel::htlc::{Htlc as Htlc, HtlcLock as HtlcLock};
+use crate::common::{
+    channelid::ChannelId,
+    duration::Duration,
+    errortype::ErrorType,
+    fee::FeeType,
+    hopdelay::HopDelay,
+    feepolicy::{Occupancy, WithInboundFee},
+    nodeid::NodeId,
+    paymentfailure::PaymentFailure,
+    paymentid::PaymentId,
+    paymentresult::PaymentResult,
+    revenue::Revenue,
+    satoshi::Satoshi,
+    timestamp::Timestamp,
+    trafficclass::TrafficClass,
+    upfrontfeepolicy::UpfrontFeePolicy,
+};
+use crate::node::Node as Node;
+use crate::node::behavior::{HtlcInfo as HtlcInfo, NodeBehavior as NodeBehavior, Resolution as Resolution};
+use crate::payment::Payment as Payment;
+use crate::reputation::{ReputationParams, ReputationTracker};
+
+mod failure;
+mod feetransfer;
+mod locks;
+mod refund;
+mod settlement;
+
+//////////////////// LNMODEL.PY //////////////////////////
+
+#[derive(Debug)]
+pub struct Network {
+    channels: HashMap<ChannelId, Channel>,
+    // for each (from, to) pair of nodes: the IDs of channels between them
+    // (neighbors are ordered to make pathfinding deterministic)
+    graph: HashMap<NodeId, BTreeMap<NodeId, Vec<ChannelId>>>,
+    channel_ends: HashMap<ChannelId, (NodeId, NodeId)>,
+    nodes: HashMap<NodeId, Node>,
+    // all fees paid so far, from payer to payee, by fee type
+    fee_flows: HashMap<(NodeId, NodeId, FeeType), Satoshi>,
+    // all fees paid so far by the senders of each traffic class, net of refunds
+    class_fees: HashMap<TrafficClass, Revenue>,
+    upfront_fee_policy: UpfrontFeePolicy,
+    // failures as reported by the erring hops (possibly spoofed)
+    num_failures: HashMap<(ChannelId, ErrorType), u64>,
+    // if set, nodes endorse HTLCs based on local reputation of their peers
+    reputation_params: Option<ReputationParams>,
+    reputations: HashMap<NodeId, ReputationTracker>,
+    // if set, slots and liquidity of every channel direction are split into buckets
+    bucket_params: Option<BucketParams>,
+    num_htlcs_per_bucket: HashMap<Bucket, u64>,
+    // if set, senders price payments by occupancy as gossiped this long ago
+    gossip_delay: Option<Duration>,
+    advertised_occupancy: HashMap<(ChannelId, Direction), Occupancy>,
+    // occupancy updates in flight, and when they reach the senders
+    pending_gossip: VecDeque<(Timestamp, ChannelId, Direction, Occupancy)>,
+    num_stale_fee_failures: u64,
+    // if set, every node runs a circuit breaker on HTLCs from each of its peers
+    circuit_breaker_params: Option<CircuitBreakerParams>,
+    // without hop delays, all hops of a payment are added and resolved at once
+    hop_delay: Option<HopDelay>,
+    // the locks of the resolved HTLCs of payments someone (e.g., an attacker) keeps track of
+    watched_payments: HashMap<PaymentId, Vec<HtlcLock>>,
+}
+
+impl Network {
+    pub fn new(upfront_fee_policy: Option<UpfrontFeePolicy>) -> Self {
+        Network {
+            channels: HashMap::new(),
+            graph: HashMap::new(),
+            channel_ends: HashMap::new(),
+            nodes: HashMap::new(),
+            fee_flows: HashMap::new(),
+            class_fees: HashMap::new(),
+            upfront_fee_policy: upfront_fee_policy.unwrap_or(UpfrontFeePolicy::KeepAll),
+            num_failures: HashMap::new(),
+            reputation_params: None,
+            reputations: HashMap::new(),
+            bucket_params: None,
+            num_htlcs_per_bucket: HashMap::new(),
+            gossip_delay: None,
+            advertised_occupancy: HashMap::new(),
+            pending_gossip: VecDeque::new(),
+            num_stale_fee_failures: 0,
+            circuit_breaker_params: None,
+            hop_delay: None,
+            watched_payments: HashMap::new(),
+        }
+    }
+
+    pub fn enable_endorsement(&mut self, reputation_params: ReputationParams) {
+        self.reputation_params = Some(reputation_params);
+    }
+
+    pub fn enable_bucketing(&mut self, bucket_params: BucketParams) {
+        // applies to channels added both before and after this call
+        for ch in self.channels.values_mut() {
+            Self::set_buckets(ch, &bucket_params);
+        }
+        self.bucket_params = Some(bucket_params);
+    }
+
+    fn set_buckets(ch: &mut Channel, bucket_params: &BucketParams) {
+        // the buckets split the whole capacity, while the direction's balance caps what they lock in total
+        let capacity = *ch.get_capacity();
+        for direction in [Direction::Alph, Direction::NonAlph] {
+            if let Some(ch_in_dir) = ch.get_channel_in_direction_mut(&direction) {
+                ch_in_dir.set_buckets(bucket_params, capacity);
+            }
+        }
+    }
+
+    pub fn enable_circuit_breaker(&mut self, circuit_breaker_params: CircuitBreakerParams) {
+        // applies to nodes added both before and after this call
+        for node in self.nodes.values_mut() {
+            node.set_circuit_breaker(circuit_breaker_params.clone());
+        }
+        self.circuit_breaker_params = Some(circuit_breaker_params);
+    }
+
+    pub fn get_circuit_breaker_drops(&self, node: &NodeId) -> BTreeMap<NodeId, u64> {
+        // how many HTLCs from each peer the node's circuit breaker has failed
+        let node = &self.nodes[node];
+        self.get_neighbors(node.get_id())
+            .map(|peer| {
+                let num_dropped = node.get_circuit_breaker(peer)
+                    .map_or(0, |circuit_breaker| circuit_breaker.get_num_dropped());
+                (peer.clone(), num_dropped)
+            })
+            .collect()
+    }
+
+    pub fn get_bucket_occupancy(&self, bucket: &Bucket, time: &Timestamp) -> BucketOccupancy {
+        // the occupancy of the bucket summed over all channel directions
+        let mut occupancy = BucketOccupancy::default();
+        for ch in self.channels.values() {
+            for direction in [Direction::Alph, Direction::NonAlph] {
+                if let Some(ch_in_dir) = ch.get_channel_in_direction(&direction) {
+                    occupancy = occupancy.add(&ch_in_dir.get_bucket_occupancy(bucket, time));
+                }
+            }
+        }
+        occupancy
+    }
+
+    pub fn set_gossip_delay(&mut self, gossip_delay: Duration) {
+        // from now on, senders only learn about occupancy changes after the delay
+        for (cid, ch) in &self.channels {
+            for direction in [Direction::Alph, Direction::NonAlph] {
+                if let Some(ch_in_dir) = ch.get_channel_in_direction(&direction) {
+                    self.advertised_occupancy.insert((cid.clone(), direction), ch_in_dir.get_occupancy());
+                }
+            }
+        }
+        self.gossip_delay = Some(gossip_delay);
+    }
+
+    pub fn set_hop_delay(&mut self, hop_delay: HopDelay) {
+        self.hop_delay = Some(hop_delay);
+    }
+
+    fn sample_hop_delays<R: Rng>(&self, num_hops: usize, rng: &mut R) -> Vec<(Duration, Duration)> {
+        // for each hop: the delay to add the HTLC downstream, and to pass its resolution upstream
+        (0..num_hops)
+            .map(|_| match &self.hop_delay {
+                Some(hop_delay) => (hop_delay.sample(rng), hop_delay.sample(rng)),
+                None => (Duration(0), Duration(0)),
+            })
+            .collect()
+    }
+
+    pub fn propagate_gossip(&mut self, time: &Timestamp) {
+        // channel directions also announce their occupancy as their HTLCs resolve, not only as HTLCs are added
+        if self.gossip_delay.is_some() {
+            self.resolve_htlcs(Some(time));
+        }
+        self.deliver_gossip(time);
+    }
+
+    fn deliver_gossip(&mut self, time: &Timestamp) {
+        // deliver the occupancy updates that have reached the senders by now
+        while self.pending_gossip.front().is_some_and(|(arrival_time, _, _, _)| arrival_time <= time) {
+            let (_, cid, direction, occupancy) = self.pending_gossip.pop_front().unwrap();
+            self.advertised_occupancy.insert((cid, direction), occupancy);
+        }
+    }
+
+    fn announce_occupancy(&mut self, route: &[NodeId], cids: &[ChannelId], time: &Timestamp) {
+        // gossip the current occupancy of the channel directions along the route
+        for (nodes, cid) in route.windows(2).zip(cids) {
+            self.announce_direction_occupancy(cid, &nodes[0], &nodes[1], time);
+        }
+        self.deliver_gossip(time);
+    }
+
+    pub(super) fn announce_direction_occupancy(&mut self, cid: &ChannelId, from: &NodeId, to: &NodeId, time: &Timestamp) {
+        let Some(gossip_delay) = &self.gossip_delay else {
+            return;
+        };
+        let Some(ch_in_dir) = self.get_channel_in_direction_of(cid, from, to) else {
+            return;
+        };
+        let arrival_time = time.add(gossip_delay);
+        let occupancy = ch_in_dir.get_occupancy();
+        // HTLCs may be released after the fact, so updates are not always announced in order
+        let index = self.pending_gossip.partition_point(|(other_arrival_time, _, _, _)| other_arrival_time <= &arrival_time);
+        self.pending_gossip.insert(index, (arrival_time, cid.clone(), Direction::new(from, to), occupancy));
+    }
+
+    fn apply_channel_update(&mut self, cid: &ChannelId, from: &NodeId, to: &NodeId) {
+        // Like a BOLT 4 channel_update, the failure tells the sender the current occupancy right away,
+        // superseding the older gossip still on its way.
+        let direction = Direction::new(from, to);
+        let occupancy = self.get_channel_in_direction_of(cid, from, to).unwrap().get_occupancy();
+        self.pending_gossip.retain(|(_, other_cid, other_direction, _)| other_cid != cid || other_direction != &direction);
+        self.advertised_occupancy.insert((cid.clone(), direction), occupancy);
+    }
+
+    fn get_advertised_occupancy(&self, cid: &ChannelId, from: &NodeId, to: &NodeId) -> Occupancy {
+        let ch_in_dir = self.get_channel_in_direction_of(cid, from, to).unwrap();
+        if self.gossip_delay.is_none() {
+            return ch_in_dir.get_occupancy();
+        }
+        self.advertised_occupancy
+            .get(&(cid.clone(), Direction::new(from, to)))
+            .copied()
+            .unwrap_or_else(|| ch_in_dir.get_occupancy())
+    }
+
+    pub fn get_num_stale_fee_failures(&self) -> u64 {
+        // payments rejected because the sender priced them by outdated occupancy
+        self.num_stale_fee_failures
+    }
+
+    pub fn get_num_htlcs_in_bucket(&self, bucket: &Bucket) -> u64 {
+        // how many HTLCs have been stored in the bucket over the whole simulation
+        *self.num_htlcs_per_bucket.get(bucket).unwrap_or(&0)
+    }
+
+    pub fn get_reputation_tracker(&self, node: &NodeId) -> Option<&ReputationTracker> {
+        self.reputations.get(node)
+    }
+
+    fn has_good_reputation(&self, node: &NodeId, peer: &NodeId, time: &Timestamp) -> bool {
+        let Some(reputation_params) = &self.reputation_params else {
+            return false;
+        };
+        match self.reputations.get(node) {
+            Some(tracker) => tracker.has_good_reputation(reputation_params, peer, time),
+            None => ReputationTracker::new().has_good_reputation(reputation_params, peer, time),
+        }
+    }
+
+    pub fn add_channel(&mut self, node_a: NodeId, node_b: NodeId, mut channel: Channel) {
+        // Direction::new panics if the node IDs are equal
+        let _direction = Direction::new(&node_a, &node_b);
+        let cid = channel.get_cid().clone();
+        assert!(!self.channels.contains_key(&cid), "Channel {:?} already exists", cid);
+        for (from, to) in [(&node_a, &node_b), (&node_b, &node_a)] {
+            self.graph
+                .entry(from.clone())
+                .or_default()
+                .entry(to.clone())
+                .or_default()
+                .push(cid.clone());
+        }
+        // nodes without channels yet are honest and start with an empty wallet
+        for node in [&node_a, &node_b] {
+            if !self.nodes.contains_key(node) {
+                self.add_node(Node::new(node.clone(), None, None));
+            }
+        }
+        self.channel_ends.insert(cid.clone(), (node_a, node_b));
+        if let Some(bucket_params) = &self.bucket_params {
+            Self::set_buckets(&mut channel, bucket_params);
+        }
+        self.channels.insert(cid, channel);
+    }
+
+    pub fn get_channel(&self, cid: &ChannelId) -> Option<&Channel> {
+        self.channels.get(cid)
+    }
+
+    pub fn get_neighbors(&self, node: &NodeId) -> impl Iterator<Item = &NodeId> {
+        self.graph.get(node).into_iter().flat_map(|neighbors| neighbors.keys())
+    }
+
+    fn get_cids(&self, from: &NodeId, to: &NodeId) -> Option<&Vec<ChannelId>> {
+        self.graph.get(from)?.get(to)
+    }
+
+    pub fn get_cids_in_direction(&self, from: &NodeId, to: &NodeId) -> impl Iterator<Item = &ChannelId> {
+        // the channels between the nodes that are enabled in this direction
+        let direction = Direction::new(from, to);
+        self.get_cids(from, to)
+            .into_iter()
+            .flatten()
+            .filter(move |cid| self.channels[*cid].is_enabled(&direction))
+    }
+
+    pub fn get_cid_in_direction(&self, from: &NodeId, to: &NodeId) -> Option<&ChannelId> {
+        // if there are multiple channels, choose the first one enabled in this direction
+        self.get_cids_in_direction(from, to).next()
+    }
+
+    pub fn get_channel_in_direction(&self, from: &NodeId, to: &NodeId) -> Option<&ChannelInDirection> {
+        let cid = self.get_cid_in_direction(from, to)?;
+        self.get_channel_in_direction_of(cid, from, to)
+    }
+
+    pub fn get_balance(&self, cid: &ChannelId, from: &NodeId, to: &NodeId) -> Satoshi {
+        *self.channels[cid].get_balance(&Direction::new(from, to))
+    }
+
+    pub fn get_channel_in_direction_of(&self, cid: &ChannelId, from: &NodeId, to: &NodeId) -> Option<&ChannelInDirection> {
+        self.channels.get(cid)?.get_channel_in_direction(&Direction::new(from, to))
+    }
+
+    pub fn pick_cids(&self, route: &[NodeId]) -> Vec<ChannelId> {
+        // For senders that don't choose channels themselves: the first channel enabled in each hop's direction.
+        // If there is none, any channel between the nodes, so that the payment fails on it.
+        route
+            .windows(2)
+            .map(|nodes| self.get_cid_in_direction(&nodes[0], &nodes[1])
+                .or_else(|| self.get_cids(&nodes[0], &nodes[1]).and_then(|cids| cids.first()))
+                .unwrap_or_else(|| panic!("No channel from {:?} to {:?}", nodes[0], nodes[1]))
+                .clone())
+            .collect()
+    }
+
+    fn get_channel_in_direction_mut(
+        &mut self,
+        cid: &ChannelId,
+        from: &NodeId,
+        to: &NodeId,
+    ) -> &mut ChannelInDirection {
+        self.channels
+            .get_mut(cid)
+            .and_then(|ch| ch.get_channel_in_direction_mut(&Direction::new(from, to)))
+            .unwrap_or_else(|| panic!("Channel {:?} is not enabled from {:?} to {:?}", cid, from, to))
+    }
+
+    pub fn add_node(&mut self, mut node: Node) {
+        // must be called before the node's channels are added
+        assert!(!self.nodes.contains_key(node.get_id()), "Node {:?} already exists", node.get_id());
+        if let Some(circuit_breaker_params) = &self.circuit_breaker_params {
+            node.set_circuit_breaker(circuit_breaker_params.clone());
+        }
+        self.nodes.insert(node.get_id().clone(), node);
+    }
+
+    pub fn set_node_behavior(&mut self, node: &NodeId, behavior: Box<dyn NodeBehavior>) {
+        self.get_node_mut(node).behavior = behavior;
+    }
+
+    pub fn get_node(&self, node: &NodeId) -> Option<&Node> {
+        self.nodes.get(node)
+    }
+
+    fn get_node_mut(&mut self, node: &NodeId) -> &mut Node {
+        self.nodes.get_mut(node).unwrap_or_else(|| panic!("No node {:?}", node))
+    }
+
+    pub fn get_revenue(&self, node: &NodeId) -> &Revenue {
+        self.nodes[node].get_revenue()
+    }
+
+    pub fn get_fee_flows(&self) -> impl Iterator<Item = (&NodeId, &NodeId, &FeeType, &Satoshi)> {
+        // (payer, payee, fee type, total fees paid)
+        self.fee_flows.iter().map(|((payer, payee, fee_type), amount)| (payer, payee, fee_type, amount))
+    }
+
+    pub fn get_class_fees(&self, traffic_class: &TrafficClass) -> Revenue {
+        // reverse hold fees are paid to the senders, so they count negative
+        self.class_fees.get(traffic_class).cloned().unwrap_or_default()
+    }
+
+    pub fn get_num_failures(&self, cid: &ChannelId, error_type: ErrorType) -> u64 {
+        *self.num_failures.get(&(cid.clone(), error_type)).unwrap_or(&0)
+    }
+
+    pub fn create_payment(
+        &self,
+        route: &[NodeId],
+        amount: Satoshi,
+        processing_delay: Duration,
+        desired_result: PaymentResult,
+    ) -> Payment {
+        self.create_payment_over(route, &self.pick_cids(route), amount, processing_delay, desired_result)
+    }
+
+    pub fn create_payment_over(
+        &self,
+        route: &[NodeId],
+        cids: &[ChannelId],
+        amount: Satoshi,
+        processing_delay: Duration,
+        desired_result: PaymentResult,
+    ) -> Payment {
+        // senders only know the occupancy that has been gossiped to them
+        self.build_payment(route, cids, amount, processing_delay, desired_result, true)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn build_payment(
+        &self,
+        route: &[NodeId],
+        cids: &[ChannelId],
+        amount: Satoshi,
+        processing_delay: Duration,
+        desired_result: PaymentResult,
+        advertised: bool,
+    ) -> Payment {
+        // Comment from Python implementation:
+        // # The fee for hop i is set by the next node, who forwards the payment along hop i + 1.
+        // # The last hop's upfront fee is set by the receiver's upstream peer.
+        // # The receiver charges no success fee.
+        assert!(route.len() >= 2, "Route must have at least two nodes, got {:?}", route);
+        let num_hops = route.len() - 1;
+        assert_eq!(cids.len(), num_hops, "Expected a channel for each hop of {:?}, got {:?}", route, cids);
+        let get_ch_in_dir = |hop: usize| self.get_channel_in_direction_of(&cids[hop], &route[hop], &route[hop + 1])
+            .unwrap_or_else(|| panic!("No channel {:?} for hop {} of {:?}", cids[hop], hop, route));
+        let occupancy = |hop: usize| {
+            if advertised {
+                self.get_advertised_occupancy(&cids[hop], &route[hop], &route[hop + 1])
+            } else {
+                get_ch_in_dir(hop).get_occupancy()
+            }
+        };
+        // the receiver may adjust the fees for HTLCs coming in via the last hop, e.g., to attract payments
+        let ch_in_dir = get_ch_in_dir(num_hops - 1);
+        let mut payment = Payment::new(
+            &WithInboundFee { outbound: ch_in_dir.upfront_fee.as_ref(), inbound: ch_in_dir.inbound_upfront_fee.as_ref() },
+            &WithInboundFee { outbound: ch_in_dir.success_fee.as_ref(), inbound: ch_in_dir.inbound_success_fee.as_ref() },
+            Some(occupancy(num_hops - 1)),
+            Some(desired_result),
+            Some(processing_delay),
+            Some(amount),
+            None,
+            None,
+        );
+        payment.hold_fee = ch_in_dir.get_hold_fee_at_resolution().cloned();
+        payment.reverse_hold_fee = ch_in_dir.reverse_hold_fee.clone();
+        for hop in (0..num_hops - 1).rev() {
+            let ch_in_dir = get_ch_in_dir(hop + 1);
+            // the forwarding node may adjust its fees for HTLCs coming in via this hop
+            let inbound_ch_in_dir = get_ch_in_dir(hop);
+            let upfront_fee = WithInboundFee {
+                outbound: ch_in_dir.upfront_fee.as_ref(),
+                inbound: inbound_ch_in_dir.inbound_upfront_fee.as_ref(),
+            };
+            let success_fee = WithInboundFee {
+                outbound: ch_in_dir.success_fee.as_ref(),
+                inbound: inbound_ch_in_dir.inbound_success_fee.as_ref(),
+            };
+            payment = Payment::new(
+                &upfront_fee,
+                &success_fee,
+                Some(occupancy(hop + 1)),
+                None,
+                None,
+                None,
+                Some(route[hop + 1].clone()),
+                Some(Box::new(payment)),
+            );
+            payment.hold_fee = ch_in_dir.get_hold_fee_at_resolution().cloned();
+            // the upstream node of this hop sets the terms for holding its HTLC
+            payment.reverse_hold_fee = inbound_ch_in_dir.reverse_hold_fee.clone();
+        }
+        payment
+    }
+
+    pub fn attempt_send_payment<R: Rng>(
+        &mut self,
+        payment: &Payment,
+        route: &[NodeId],
+        time: &Timestamp,
+        rng: &mut R,
+    ) -> Result<PaymentId, PaymentFailure> {
+        let cids = self.pick_cids(route);
+        self.attempt_send_payment_over(payment, route, &cids, time, rng).map(|(payment_id, _)| payment_id)
+    }
+
+    pub fn attempt_send_payment_over<R: Rng>(
+        &mut self,
+        payment: &Payment,
+        route: &[NodeId],
+        cids: &[ChannelId],
+        time: &Timestamp,
+        rng: &mut R,
+    ) -> Result<(PaymentId, Timestamp), PaymentFailure> {
+        // The payment is forwarded over the given channel at each hop.
+        // On success, return the payment ID and when the payment reaches the receiver.
+        self.propagate_gossip(time);
+        let result = self.forward_payment(payment, route, cids, time, rng);
+        self.announce_occupancy(route, cids, time);
+        result
+    }
+
+    fn forward_payment<R: Rng>(
+        &mut self,
+        payment: &Payment,
+        route: &[NodeId],
+        cids: &[ChannelId],
+        time: &Timestamp,
+        rng: &mut R,
+    ) -> Result<(PaymentId, Timestamp), PaymentFailure> {
+        // Forward the payment hop by hop, storing an HTLC and paying the upfront fee at each hop.
+        // If some hop fails, unwind the upstream hops and report where and why.
+        // On success, return the ID that identifies the payment's HTLCs, and its arrival time at the receiver.
+        let payment_id = PaymentId::new();
+        // The HTLC reaches each hop later than the previous one, and the receiver last.
+        // Once the receiver resolves it, the resolution travels back, so upstream hops resolve later.
+        let hop_delays = self.sample_hop_delays(route.len() - 1, rng);
+        let arrival_delay = Duration(hop_delays.iter().map(|(add_delay, _)| add_delay.0).sum());
+        let resolution_delays: Vec<Duration> = (0..hop_delays.len())
+            .map(|hop| Duration(hop_delays[hop..].iter().map(|(_, resolve_delay)| resolve_delay.0).sum()))
+            .collect();
+        // A failure at some hop travels back the same way, starting from when the erring node fails the HTLC:
+        // the HTLC at each upstream hop is released once the failure reaches it.
+        let get_failure_times = |erring_hop: usize, failure_time: &Timestamp| -> Vec<Timestamp> {
+            (0..erring_hop)
+                .map(|hop| failure_time.add(&Duration(resolution_delays[hop].0 - resolution_delays[erring_hop].0)))
+                .collect()
+        };
+        // when the receiver resolves the payment
+        let mut resolution_time = time.add(&arrival_delay).add(&payment.processing_delay);
+        // HTLCs queued by circuit breakers reach the downstream hops later
+        let mut hop_time = time.clone();
+        if self.reputation_params.is_some() {
+            // the forwarding nodes' reputation trackers must reflect all HTLCs resolved so far
+            self.resolve_htlcs_into(&route[1..route.len() - 1], time);
+        }
+        // senders endorse their own payments
+        let mut endorsed = self.reputation_params.is_some();
+        // for each hop the HTLC has been stored at: the channel, the HTLC, and the upfront fee paid
+        let mut forwarded: Vec<(ChannelId, Htlc, Satoshi)> = Vec::new();
+        let hop_payments: Vec<&Payment> = std::iter::successors(Some(payment), |p| p.get_downstream_payment())
+            .collect();
+        // with gossip delays, the sender may have priced the payment by outdated occupancy
+        let current_payment = self.gossip_delay.is_some().then(|| {
+            let last_hop_payment = hop_payments.last().unwrap();
+            self.build_payment(
+                route,
+                cids,
+                last_hop_payment.body,
+                last_hop_payment.processing_delay.clone(),
+                last_hop_payment.desired_result.clone(),
+                false,
+            )
+        });
+        let current_hop_payments: Vec<&Payment> = current_payment.iter()
+            .flat_map(|p| std::iter::successors(Some(p), |p| p.get_downstream_payment()))
+            .collect();
+        let mut hop_payment = Some(payment);
+        for (hop, nodes) in route.windows(2).enumerate() {
+            let (upstream, downstream) = (&nodes[0], &nodes[1]);
+            let p = hop_payment.expect("Route is longer than the payment");
+            let cid = cids[hop].clone();
+            if self.get_channel_in_direction_of(&cid, upstream, downstream).is_none() {
+                // the channel has been disabled since the sender looked at the graph
+                return Err(self.fail_payment(route, &forwarded, ErrorType::ChannelDisabled, hop, cid, &get_failure_times(hop, &hop_time)));
+            }
+            // the fees priced by this channel direction must cover its current occupancy:
+            // the upstream hop's fee, and the last hop's fee, which this channel direction sets itself
+            if !current_hop_payments.is_empty() {
+                let is_last_hop = hop + 1 == hop_payments.len();
+                let priced_hops = [(hop > 0).then(|| hop - 1), is_last_hop.then_some(hop)];
+                let is_stale = priced_hops.into_iter().flatten().any(|priced_hop| {
+                    Self::get_hop_upfront_fee(&hop_payments, priced_hop)
+                        .lt(&Self::get_hop_upfront_fee(&current_hop_payments, priced_hop))
+                });
+                if is_stale {
+                    self.num_stale_fee_failures += 1;
+                    self.apply_channel_update(&cid, upstream, downstream);
+                    return Err(self.fail_payment(route, &forwarded, ErrorType::LowFee, hop, cid, &get_failure_times(hop, &hop_time)));
+                }
+            }
+            // forwarding nodes only keep the endorsement of peers with good reputation
+            if hop > 0 {
+                endorsed = endorsed && self.has_good_reputation(upstream, &route[hop - 1], time);
+            }
+            let downstream_success_fee = p.get_downstream_payment()
+                .map_or(Satoshi(0), |dp| dp.success_fee_satoshi);
+            let mut htlc = Htlc {
+                hop,
+                endorsed,
+                added_at: hop_time.clone(),
+                forwarding_fee: p.success_fee_satoshi.sub(&downstream_success_fee),
+                amount: p.get_amount(),
+                upfront_fee: p.upfront_fee_satoshi,
+                hold_fees: hop_payments[hop..].iter()
+                    .filter_map(|dp| dp.hold_fee.clone().map(|hold_fee| (hold_fee, dp.get_amount())))
+                    .collect(),
+                reverse_hold_fees: hop_payments[..=hop].iter()
+                    .filter_map(|up| up.reverse_hold_fee.clone().map(|hold_fee| (hold_fee, up.get_amount())))
+                    .collect(),
+                traffic_class: p.traffic_class,
+                resolution_delay: resolution_delays[hop].clone(),
+                ..Htlc::new(
+                    payment_id.clone(),
+                    p.success_fee_satoshi,
+                    p.desired_result.clone(),
+                )
+            };
+            // forwarding nodes decide whether to pass the HTLC on at all
+            if hop > 0 {
+                let htlc_info = HtlcInfo {
+                    payment_id: payment_id.clone(),
+                    peer: downstream.clone(),
+                    amount: htlc.amount,
+                    time: hop_time.clone(),
+                };
+                if let Err(error_type) = self.get_node_mut(upstream).behavior.on_forward(&htlc_info, rng) {
+                    return Err(self.fail_payment(route, &forwarded, error_type, hop, cid, &get_failure_times(hop, &hop_time)));
+                }
+            }
+            // the downstream node's circuit breaker limits HTLCs from the upstream node over all their channels
+            let pending_resolution_times: Vec<Timestamp> = self.get_cids_in_direction(upstream, downstream)
+                .filter_map(|cid| self.get_channel_in_direction_of(cid, upstream, downstream))
+                .flat_map(|ch_in_dir| ch_in_dir.get_htlcs().map(|(_, resolution_time)| resolution_time.clone()))
+                .collect();
+            let downstream_node = self.nodes.get_mut(downstream).unwrap_or_else(|| panic!("No node {:?}", downstream));
+            let admit = || downstream_node.admit_htlc(upstream, &hop_time, pending_resolution_times.iter());
+            let ch_in_dir = self.channels
+                .get_mut(&cid)
+                .and_then(|ch| ch.get_channel_in_direction_mut(&Direction::new(upstream, downstream)))
+                .unwrap_or_else(|| panic!("Channel {:?} is not enabled from {:?} to {:?}", cid, upstream, downstream));
+            let expected_resolution_time = resolution_time.add(&resolution_delays[hop]);
+            // the HTLC is stored right away, and stays until its resolution has traveled back to this hop
+            match ch_in_dir.add_htlc_if_admitted(time, expected_resolution_time.clone(), &mut htlc, rng, admit) {
+                Ok(released_htlcs) => {
+                    let hop_resolution_time = ch_in_dir.get_htlc_resolution_time(&htlc).unwrap().clone();
+                    if hop_resolution_time > expected_resolution_time {
+                        // the HTLC has been queued: the whole payment resolves later
+                        let queue_delay = Duration(hop_resolution_time.0 - expected_resolution_time.0);
+                        hop_time = hop_time.add(&queue_delay);
+                        resolution_time = resolution_time.add(&queue_delay);
+                        for (upstream_hop, (upstream_cid, upstream_htlc, _)) in forwarded.iter().enumerate() {
+                            self.get_channel_in_direction_mut(upstream_cid, &route[upstream_hop], &route[upstream_hop + 1])
+                                .postpone_htlc(upstream_htlc, resolution_time.add(&resolution_delays[upstream_hop]));
+                        }
+                    }
+                    *self.num_htlcs_per_bucket.entry(htlc.bucket).or_insert(0) += 1;
+                    for (released_htlc, released_resolution_time) in released_htlcs {
+                        self.resolve_htlc(&cid, upstream, downstream, released_htlc, &released_resolution_time);
+                    }
+                    self.transfer_fee(FeeType::Upfront, upstream, downstream, &p.upfront_fee_satoshi, &htlc);
+                    let htlc_info = HtlcInfo {
+                        payment_id: payment_id.clone(),
+                        peer: upstream.clone(),
+                        amount: htlc.amount,
+                        time: hop_time.clone(),
+                    };
+                    self.get_node_mut(downstream).behavior.on_add(&htlc_info);
+                    forwarded.push((cid, htlc, p.upfront_fee_satoshi));
+                    hop_time = hop_time.add(&hop_delays[hop].0);
+                }
+                Err(error_type) => {
+                    return Err(self.fail_payment(route, &forwarded, error_type, hop, cid, &get_failure_times(hop, &hop_time)));
+                }
+            }
+            hop_payment = p.get_downstream_payment();
+        }
+        self.hold_at_receiver(route, &mut forwarded, &hop_time, &resolution_time, &resolution_delays);
+        Ok((payment_id, hop_time))
+    }
+
+    fn hold_at_receiver(
+        &mut self,
+        route: &[NodeId],
+        forwarded: &mut [(ChannelId, Htlc, Satoshi)],
+        arrival_time: &Timestamp,
+        resolution_time: &Timestamp,
+        resolution_delays: &[Duration],
+    ) {
+        // The receiver decides when and how to resolve the payment, which may differ from what the sender asked for.
+        // Then the HTLCs at all hops are resolved accordingly.
+        let (_, last_htlc, _) = forwarded.last().unwrap();
+        let htlc_info = HtlcInfo {
+            payment_id: last_htlc.payment_id.clone(),
+            peer: route[route.len() - 2].clone(),
+            amount: last_htlc.amount,
+            time: arrival_time.clone(),
+        };
+        let requested = Resolution {
+            hold_time: Duration(resolution_time.0 - arrival_time.0),
+            result: last_htlc.desired_result.clone(),
+        };
+        let resolution = self.get_node_mut(route.last().unwrap()).behavior.on_hold(&htlc_info, requested.clone());
+        if resolution == requested {
+            return;
+        }
+        let new_resolution_time = arrival_time.add(&resolution.hold_time);
+        for (hop, (cid, htlc, _)) in forwarded.iter_mut().enumerate() {
+            let ch_in_dir = self.get_channel_in_direction_mut(cid, &route[hop], &route[hop + 1]);
+            let (mut stored_htlc, _) = ch_in_dir.remove_htlc(htlc)
+                .unwrap_or_else(|| panic!("HTLC {:?} not found in channel {:?}", htlc, cid));
+            stored_htlc.desired_result = resolution.result.clone();
+            ch_in_dir.push_htlc(new_resolution_time.add(&resolution_delays[hop]), stored_htlc.clone());
+            *htlc = stored_htlc;
+        }
+    }
+
+    fn get_hop_upfront_fee(hop_payments: &[&Payment], hop: usize) -> Satoshi {
+        // upfront fees in the onion are cumulative: subtract what is passed on downstream
+        let downstream_upfront_fee = hop_payments.get(hop + 1).map_or(Satoshi(0), |dp| dp.upfront_fee_satoshi);
+        hop_payments[hop].upfront_fee_satoshi.sub(&downstream_upfront_fee)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel::circuitbreaker::CircuitBreakerMode;
+    use crate::node::behavior::{DeliberateFailer, Jammer};
+    use crate::common::fee::{Fee, FeeRate};
+    use crate::common::holdfee::HoldFee;
+    use crate::common::feepolicy::{CongestionFee, HoldTimeFee, InboundFee, PricingCurve};
+    use crate::testutils::{self, cid, node};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn route() -> Vec<NodeId> {
+        vec![node("Alice"), node("Bob"), node("Charlie"), node("Dave")]
+    }
+
+    fn add_channel(
+        network: &mut Network,
+        from: &str,
+        to: &str,
+        num_slots: usize,
+        deliberately_fail_prob: f64,
+        spoofing_error_type: Option<ErrorType>,
+    ) {
+        let ch_in_dir = ChannelInDirection::new(
+            Some(num_slots),
+            Some(Box::new(Fee::new(Satoshi(2), FeeRate(0.02)))),
+            Some(Box::new(Fee::new(Satoshi(5), FeeRate(0.05)))),
+            Some(deliberately_fail_prob),
+            spoofing_error_type,
+        );
+        testutils::add_channel(network, from, to, ch_in_dir);
+    }
+
+    fn example_network(
+        upfront_fee_policy: Option<UpfrontFeePolicy>,
+        deliberately_fail_prob: f64,
+    ) -> Network {
+        // Alice - Bob - Charlie - Dave, the last hop fails deliberately with the given probability
+        let mut network = Network::new(upfront_fee_policy);
+        add_channel(&mut network, "Alice", "Bob", 2, 0.0, None);
+        add_channel(&mut network, "Bob", "Charlie", 2, 0.0, None);
+        add_channel(&mut network, "Charlie", "Dave", 2, deliberately_fail_prob, Some(ErrorType::NoSlots));
+        network
+    }
+
+    fn assert_upfront_revenues(network: &Network, expected: [i64; 4]) {
+        for (node, revenue) in route().iter().zip(expected) {
+            assert_eq!(network.get_revenue(node).upfront, revenue);
+        }
+    }
+
+    fn assert_num_slots_busy(network: &Network, expected: [usize; 3]) {
+        for (nodes, num_slots_busy) in route().windows(2).zip(expected) {
+            let ch_in_dir = network.get_channel_in_direction(&nodes[0], &nodes[1]).unwrap();
+            assert_eq!(ch_in_dir.get_num_slots_busy(), num_slots_busy);
+        }
+    }
+
+    #[test]
+    fn network_create_payment() {
+        // same numbers as in payment_simple
+        let network = example_network(None, 0.0);
+        let payment = network.create_payment(
+            &route(), Satoshi(100), Duration(1), PaymentResult::SUCCESS);
+        assert_eq!(payment.body, Satoshi(110));
+        assert_eq!(payment.success_fee_satoshi, Satoshi(20));
+        assert_eq!(payment.upfront_fee_satoshi, Satoshi(12));
+    }
+
+    #[test]
+    fn network_send_payment_success() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = example_network(None, 0.0);
+        let payment = network.create_payment(
+            &route(), Satoshi(100), Duration(1), PaymentResult::SUCCESS);
+        let result = network.attempt_send_payment(&payment, &route(), &Timestamp(0), &mut rng);
+        assert!(result.is_ok());
+        assert_num_slots_busy(&network, [1, 1, 1]);
+        // Alice pays 12, Bob forwards 8, Charlie forwards 4
+        assert_upfront_revenues(&network, [-12, 4, 4, 4]);
+        // success fees are paid only when HTLCs are resolved
+        assert_eq!(network.get_revenue(&node("Alice")).success, 0);
+    }
+
+    #[test]
+    fn network_send_payment_failure() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = example_network(None, 1.0);
+        let payment = network.create_payment(
+            &route(), Satoshi(100), Duration(1), PaymentResult::SUCCESS);
+        let failure = network.attempt_send_payment(&payment, &route(), &Timestamp(0), &mut rng)
+            .unwrap_err();
+        // the sender sees the spoofed error type
+        assert_eq!(failure, PaymentFailure {
+            error_type: ErrorType::NoSlots,
+            erring_hop: 2,
+            erring_channel: cid("Charlie", "Dave"),
+        });
+        assert_eq!(network.get_num_failures(&cid("Charlie", "Dave"), ErrorType::NoSlots), 1);
+        assert_eq!(network.get_num_failures(&cid("Charlie", "Dave"), ErrorType::FailedDeliberately), 0);
+        // upstream HTLCs stay in flight until the failure reaches them, upstream nodes keep upfront fees
+        assert_num_slots_busy(&network, [1, 1, 0]);
+        network.resolve_all_htlcs();
+        assert_num_slots_busy(&network, [0, 0, 0]);
+        assert_upfront_revenues(&network, [-12, 4, 8, 0]);
+    }
+
+    #[test]
+    fn network_send_payment_failure_refund_erring_hop() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = example_network(Some(UpfrontFeePolicy::RefundErringHop), 1.0);
+        let payment = network.create_payment(
+            &route(), Satoshi(100), Duration(1), PaymentResult::SUCCESS);
+        let result = network.attempt_send_payment(&payment, &route(), &Timestamp(0), &mut rng);
+        assert!(result.is_err());
+        // Charlie refunds the upfront fee he got from Bob
+        assert_upfront_revenues(&network, [-12, 12, 0, 0]);
+    }
+
+    #[test]
+    fn network_refund_fast_resolution() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let policy = UpfrontFeePolicy::RefundFastResolution {
+            full_refund_time: Duration(5),
+            no_refund_time: Duration(25),
+        };
+        let mut network = example_network(Some(policy), 0.0);
+        // a fast payment is refunded in full
+        let payment = network.create_payment(
+            &route(), Satoshi(100), Duration(1), PaymentResult::SUCCESS);
+        assert!(network.attempt_send_payment(&payment, &route(), &Timestamp(0), &mut rng).is_ok());
+        network.resolve_all_htlcs();
+        assert_upfront_revenues(&network, [0, 0, 0, 0]);
+        // a jam pays the full upfront fees, a slower payment half of them
+        for hold_time in [25, 15] {
+            let payment = network.create_payment(
+                &route(), Satoshi(100), Duration(hold_time), PaymentResult::FAILURE);
+            assert!(network.attempt_send_payment(&payment, &route(), &Timestamp(0), &mut rng).is_ok());
+        }
+        network.resolve_all_htlcs();
+        assert_upfront_revenues(&network, [-12 - 6, 4 + 2, 4 + 2, 4 + 2]);
+    }
+
+    #[test]
+    fn network_send_payment_failure_refund_fast_resolution() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let policy = UpfrontFeePolicy::RefundFastResolution {
+            full_refund_time: Duration(5),
+            no_refund_time: Duration(25),
+        };
+        let mut network = example_network(Some(policy), 1.0);
+        let payment = network.create_payment(
+            &route(), Satoshi(100), Duration(1), PaymentResult::SUCCESS);
+        assert!(network.attempt_send_payment(&payment, &route(), &Timestamp(0), &mut rng).is_err());
+        // without hop delays, the failure reaches upstream HTLCs right away, so their upfront fees are refunded
+        network.resolve_all_htlcs();
+        assert_upfront_revenues(&network, [0, 0, 0, 0]);
+    }
+
+    fn circuit_breaker_params(mode: CircuitBreakerMode) -> CircuitBreakerParams {
+        CircuitBreakerParams {
+            max_pending_htlcs: 1,
+            burst: 10,
+            rate: 1.0,
+            mode,
+        }
+    }
+
+    #[test]
+    fn network_circuit_breaker_fail() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = example_network(None, 0.0);
+        network.enable_circuit_breaker(circuit_breaker_params(CircuitBreakerMode::Fail));
+        let payment = network.create_payment(
+            &route(), Satoshi(100), Duration(10), PaymentResult::SUCCESS);
+        assert!(network.attempt_send_payment(&payment, &route(), &Timestamp(0), &mut rng).is_ok());
+        // Bob only lets one HTLC from Alice be pending
+        let failure = network.attempt_send_payment(&payment, &route(), &Timestamp(0), &mut rng).unwrap_err();
+        assert_eq!(failure.error_type, ErrorType::RateLimited);
+        assert_eq!(failure.erring_channel, cid("Alice", "Bob"));
+        assert_eq!(
+            network.get_circuit_breaker_drops(&node("Bob")),
+            BTreeMap::from([(node("Alice"), 1), (node("Charlie"), 0)]),
+        );
+        // once the first payment has resolved, the next one goes through
+        assert!(network.attempt_send_payment(&payment, &route(), &Timestamp(10), &mut rng).is_ok());
+    }
+
+    #[test]
+    fn network_settlement_moves_balances() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = example_network(None, 0.0);
+        let balance = |network: &Network, from: &str, to: &str| network.get_balance(&cid(from, to), &node(from), &node(to));
+        // each side starts with half of the capacity
+        assert_eq!(balance(&network, "Alice", "Bob"), Satoshi(500_000));
+        let payment = network.create_payment(
+            &route(), Satoshi(300_000), Duration(1), PaymentResult::SUCCESS);
+        assert!(network.attempt_send_payment(&payment, &route(), &Timestamp(0), &mut rng).is_ok());
+        // the amount (with the downstream fees) moves to Bob once the payment settles
+        let amount = payment.get_amount();
+        assert_eq!(balance(&network, "Alice", "Bob"), Satoshi(500_000));
+        network.resolve_all_htlcs();
+        assert_eq!(balance(&network, "Alice", "Bob"), Satoshi(500_000 - amount.0));
+        assert_eq!(balance(&network, "Charlie", "Dave"), Satoshi(200_000));
+        // Alice can't send as much again
+        let failure = network.attempt_send_payment(&payment, &route(), &Timestamp(1), &mut rng).unwrap_err();
+        assert_eq!(failure.error_type, ErrorType::LowBalance);
+        assert_eq!(failure.erring_hop, 0);
+    }
+
+    #[test]
+    fn network_circuit_breaker_per_peer() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = example_network(None, 0.0);
+        // a second channel from Alice to Bob doesn't get Alice around Bob's limits
+        let mut ch = Channel::new(ChannelId(String::from("AliceBob2")), testutils::CAPACITY);
+        let ch_in_dir = ChannelInDirection::new(Some(2), None, None, None, None);
+        ch.set_channel_in_direction(Direction::new(&node("Alice"), &node("Bob")), Some(ch_in_dir));
+        network.add_channel(node("Alice"), node("Bob"), ch);
+        network.enable_circuit_breaker(circuit_breaker_params(CircuitBreakerMode::Fail));
+        let payment = network.create_payment(
+            &route(), Satoshi(100), Duration(10), PaymentResult::SUCCESS);
+        assert!(network.attempt_send_payment(&payment, &route(), &Timestamp(0), &mut rng).is_ok());
+        let cids = vec![ChannelId(String::from("AliceBob2")), cid("Bob", "Charlie"), cid("Charlie", "Dave")];
+        let failure = network.attempt_send_payment_over(&payment, &route(), &cids, &Timestamp(0), &mut rng).unwrap_err();
+        assert_eq!(failure.error_type, ErrorType::RateLimited);
+        assert_eq!(failure.erring_channel, ChannelId(String::from("AliceBob2")));
+        assert_eq!(network.get_circuit_breaker_drops(&node("Bob"))[&node("Alice")], 1);
+    }
+
+    #[test]
+    fn network_circuit_breaker_ignores_htlcs_that_dont_fit() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = example_network(None, 0.0);
+        network.enable_circuit_breaker(CircuitBreakerParams {
+            max_pending_htlcs: 10,
+            burst: 2,
+            rate: 0.01,
+            mode: CircuitBreakerMode::Fail,
+        });
+        let payment = network.create_payment(
+            &route(), Satoshi(100), Duration(10), PaymentResult::SUCCESS);
+        for _ in 0..2 {
+            assert!(network.attempt_send_payment(&payment, &route(), &Timestamp(0), &mut rng).is_ok());
+        }
+        // with Alice - Bob full, the HTLC fails for lack of slots and uses up none of Bob's tokens for Alice
+        let failure = network.attempt_send_payment(&payment, &route(), &Timestamp(0), &mut rng).unwrap_err();
+        assert_eq!(failure.error_type, ErrorType::NoSlots);
+        assert_eq!(network.get_circuit_breaker_drops(&node("Bob"))[&node("Alice")], 0);
+    }
+
+    #[test]
+    fn network_circuit_breaker_queue() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = example_network(None, 0.0);
+        network.enable_circuit_breaker(circuit_breaker_params(CircuitBreakerMode::Queue));
+        let payment = network.create_payment(
+            &route(), Satoshi(100), Duration(10), PaymentResult::SUCCESS);
+        for _ in 0..2 {
+            assert!(network.attempt_send_payment(&payment, &route(), &Timestamp(0), &mut rng).is_ok());
+        }
+        // the second payment waits at Bob until the first one resolves, and so resolves later at every hop
+        for nodes in route().windows(2) {
+            let ch_in_dir = network.get_channel_in_direction(&nodes[0], &nodes[1]).unwrap();
+            assert_eq!(ch_in_dir.get_num_unendorsed_htlcs_in_flight(&Timestamp(15)), 1);
+            assert_eq!(ch_in_dir.get_num_unendorsed_htlcs_in_flight(&Timestamp(20)), 0);
+        }
+        assert_eq!(network.get_node(&node("Bob")).unwrap()
+            .get_circuit_breaker(&node("Alice")).unwrap().get_num_queued(), 1);
+    }
+
+    #[test]
+    fn network_hop_delays() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = example_network(None, 0.0);
+        network.set_hop_delay(HopDelay::new(Duration(2), Duration(2)));
+        let payment = network.create_payment(
+            &route(), Satoshi(100), Duration(10), PaymentResult::SUCCESS);
+        assert!(network.attempt_send_payment(&payment, &route(), &Timestamp(0), &mut rng).is_ok());
+        // Dave gets the payment at time 6 and settles it at time 16, then each hop adds 2 on the way back
+        let resolution_times = |network: &Network| -> Vec<Timestamp> {
+            route().windows(2).map(|nodes| network.get_channel_in_direction(&nodes[0], &nodes[1]).unwrap()
+                .get_earliest_htlc_resolution_time().clone()).collect()
+        };
+        assert_eq!(resolution_times(&network), vec![Timestamp(22), Timestamp(20), Timestamp(18)]);
+        // a receiver that holds the payment shifts all hops, keeping them staggered
+        network.set_node_behavior(&node("Dave"), Box::new(Jammer { hold_time: Duration(100) }));
+        network.resolve_all_htlcs();
+        assert!(network.attempt_send_payment(&payment, &route(), &Timestamp(0), &mut rng).is_ok());
+        assert_eq!(resolution_times(&network), vec![Timestamp(112), Timestamp(110), Timestamp(108)]);
+    }
+
+    #[test]
+    fn network_node_deliberate_failer() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = example_network(None, 0.0);
+        network.set_node_behavior(&node("Charlie"), Box::new(DeliberateFailer::new(1.0, Some(ErrorType::NoSlots))));
+        let payment = network.create_payment(
+            &route(), Satoshi(100), Duration(1), PaymentResult::SUCCESS);
+        let failure = network.attempt_send_payment(&payment, &route(), &Timestamp(0), &mut rng).unwrap_err();
+        assert_eq!(failure, PaymentFailure {
+            error_type: ErrorType::NoSlots,
+            erring_hop: 2,
+            erring_channel: cid("Charlie", "Dave"),
+        });
+        assert_num_slots_busy(&network, [1, 1, 0]);
+        network.resolve_all_htlcs();
+        assert_num_slots_busy(&network, [0, 0, 0]);
+    }
+
+    #[test]
+    fn network_node_jammer_receiver() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = example_network(None, 0.0);
+        network.set_node_behavior(&node("Dave"), Box::new(Jammer { hold_time: Duration(100) }));
+        let payment = network.create_payment(
+            &route(), Satoshi(100), Duration(1), PaymentResult::SUCCESS);
+        assert!(network.attempt_send_payment(&payment, &route(), &Timestamp(0), &mut rng).is_ok());
+        // Dave holds the payment at every hop for much longer than asked, and then fails it
+        for nodes in route().windows(2) {
+            let ch_in_dir = network.get_channel_in_direction(&nodes[0], &nodes[1]).unwrap();
+            assert_eq!(ch_in_dir.get_earliest_htlc_resolution_time(), &Timestamp(100));
+        }
+        network.resolve_all_htlcs();
+        for node in route() {
+            assert_eq!(network.get_revenue(&node).success, 0);
+        }
+    }
+
+    #[test]
+    fn network_node_balance() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = Network::new(None);
+        network.add_node(Node::new(node("Alice"), Some(Satoshi(1000)), None));
+        add_channel(&mut network, "Alice", "Bob", 2, 0.0, None);
+        add_channel(&mut network, "Bob", "Charlie", 2, 0.0, None);
+        add_channel(&mut network, "Charlie", "Dave", 2, 0.0, None);
+        let payment = network.create_payment(
+            &route(), Satoshi(100), Duration(1), PaymentResult::SUCCESS);
+        assert!(network.attempt_send_payment(&payment, &route(), &Timestamp(0), &mut rng).is_ok());
+        network.resolve_all_htlcs();
+        // Alice pays 12 upfront and 20 on success
+        assert_eq!(network.get_node(&node("Alice")).unwrap().get_balance(), 1000 - 12 - 20);
+        assert_eq!(network.get_node(&node("Bob")).unwrap().get_balance(), 4 + 10);
+    }
+
+    #[test]
+    fn network_circular_route() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = Network::new(None);
+        add_channel(&mut network, "Alice", "Bob", 5, 0.0, None);
+        add_channel(&mut network, "Bob", "Charlie", 3, 0.0, None);
+        add_channel(&mut network, "Charlie", "Alice", 5, 0.0, None);
+        let circular_route = vec![
+            node("Alice"), node("Bob"), node("Charlie"), node("Alice"), node("Bob"), node("Charlie"), node("Alice"),
+        ];
+        let num_slots_busy = |network: &Network| network
+            .get_channel_in_direction(&node("Bob"), &node("Charlie")).unwrap().get_num_slots_busy();
+        let payment = network.create_payment(
+            &circular_route, Satoshi(100), Duration(10), PaymentResult::FAILURE);
+        assert!(network.attempt_send_payment(&payment, &circular_route, &Timestamp(0), &mut rng).is_ok());
+        // one payment holds two slots of the channel direction it passes twice
+        assert_eq!(num_slots_busy(&network), 2);
+        // the next one only finds room for one pass, and holds it until its failure is resolved
+        let failure = network.attempt_send_payment(&payment, &circular_route, &Timestamp(0), &mut rng).unwrap_err();
+        assert_eq!(failure.error_type, ErrorType::NoSlots);
+        assert_eq!(failure.erring_hop, 4);
+        assert_eq!(num_slots_busy(&network), 3);
+        network.resolve_all_htlcs();
+        assert_eq!(num_slots_busy(&network), 0);
+    }
+
+    #[test]
+    fn network_send_payment_channel_disabled() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = example_network(None, 0.0);
+        let payment = network.create_payment(
+            &route(), Satoshi(100), Duration(1), PaymentResult::SUCCESS);
+        // Bob disables his channel to Charlie after the payment has been created
+        let ch = network.channels.get_mut(&cid("Bob", "Charlie")).unwrap();
+        ch.set_channel_in_direction(Direction::new(&node("Bob"), &node("Charlie")), None);
+        let failure = network.attempt_send_payment(&payment, &route(), &Timestamp(0), &mut rng)
+            .unwrap_err();
+        assert_eq!(failure.error_type, ErrorType::ChannelDisabled);
+        assert_eq!(failure.erring_hop, 1);
+        assert_eq!(failure.erring_channel, cid("Bob", "Charlie"));
+        assert!(failure.error_type.has_channel_update());
+        assert_num_slots_busy_first_hop(&network, 1);
+        network.resolve_all_htlcs();
+        assert_num_slots_busy_first_hop(&network, 0);
+    }
+
+    #[test]
+    fn network_failure_staggered_upstream() {
+        // each hop takes 2 time units to add an HTLC and 2 more to pass the failure back
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = example_network(None, 1.0);
+        network.set_hop_delay(HopDelay::new(Duration(2), Duration(2)));
+        let payment = network.create_payment(
+            &route(), Satoshi(100), Duration(1), PaymentResult::SUCCESS);
+        assert!(network.attempt_send_payment(&payment, &route(), &Timestamp(0), &mut rng).is_err());
+        // Charlie fails the payment at 4: Bob's HTLC is released at 6, Alice's at 8
+        let resolution_times: Vec<Timestamp> = route()[..3].windows(2)
+            .map(|nodes| network.get_channel_in_direction(&nodes[0], &nodes[1]).unwrap()
+                .get_earliest_htlc_resolution_time().clone())
+            .collect();
+        assert_eq!(resolution_times, vec![Timestamp(8), Timestamp(6)]);
+        network.resolve_all_htlcs();
+        assert_num_slots_busy(&network, [0, 0, 0]);
+        for node in route() {
+            assert_eq!(network.get_revenue(&node).success, 0);
+        }
+    }
+
+    fn assert_num_slots_busy_first_hop(network: &Network, expected: usize) {
+        let ch_in_dir = network.get_channel_in_direction(&node("Alice"), &node("Bob")).unwrap();
+        assert_eq!(ch_in_dir.get_num_slots_busy(), expected);
+    }
+
+    #[test]
+    fn network_fail_payment_at() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = example_network(None, 0.0);
+        let payment = network.create_payment(
+            &route(), Satoshi(100), Duration(10), PaymentResult::SUCCESS);
+        let payment_id = network.attempt_send_payment(&payment, &route(), &Timestamp(0), &mut rng)
+            .unwrap();
+        network.fail_payment_at(&payment_id, &route(), &Timestamp(3));
+        assert_num_slots_busy(&network, [1, 1, 1]);
+        let ch_in_dir = network.get_channel_in_direction(&node("Alice"), &node("Bob")).unwrap();
+        assert_eq!(ch_in_dir.get_earliest_htlc_resolution_time(), &Timestamp(3));
+        // no success fees are paid when the HTLCs are resolved
+        network.resolve_all_htlcs();
+        for node in route() {
+            assert_eq!(network.get_revenue(&node).success, 0);
+        }
+    }
+
+    #[test]
+    fn network_resolve_all_htlcs() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = example_network(None, 0.0);
+        let payment = network.create_payment(
+            &route(), Satoshi(100), Duration(1), PaymentResult::SUCCESS);
+        assert!(network.attempt_send_payment(&payment, &route(), &Timestamp(0), &mut rng).is_ok());
+        network.resolve_all_htlcs();
+        assert_num_slots_busy(&network, [0, 0, 0]);
+        // Alice pays 20, Bob forwards 10, Charlie forwards nothing (no success fee on the last hop)
+        let success_revenues: Vec<i64> = route().iter()
+            .map(|node| network.get_revenue(node).success)
+            .collect();
+        assert_eq!(success_revenues, vec![-20, 10, 10, 0]);
+    }
+
+    #[test]
+    fn network_resolve_released_htlcs() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = Network::new(None);
+        add_channel(&mut network, "Alice", "Bob", 1, 0.0, None);
+        let route = vec![node("Alice"), node("Bob")];
+        let payment = network.create_payment(
+            &route, Satoshi(100), Duration(5), PaymentResult::SUCCESS);
+        assert!(network.attempt_send_payment(&payment, &route, &Timestamp(0), &mut rng).is_ok());
+        // the only slot is busy until time 5
+        let failure = network.attempt_send_payment(&payment, &route, &Timestamp(3), &mut rng)
+            .unwrap_err();
+        assert_eq!(failure.error_type, ErrorType::NoSlots);
+        assert_eq!(failure.erring_hop, 0);
+        // at time 5, the first HTLC is released and resolved
+        assert!(network.attempt_send_payment(&payment, &route, &Timestamp(5), &mut rng).is_ok());
+        // there are no success fees on a single-hop route, but upfront fees are paid twice
+        assert_eq!(network.get_revenue(&node("Alice")).success, 0);
+        assert_eq!(network.get_revenue(&node("Bob")).upfront, 8);
+    }
+
+    fn reputation_params() -> ReputationParams {
+        ReputationParams {
+            revenue_window: Duration(200),
+            reputation_multiplier: 10,
+            resolution_period: Duration(10),
+        }
+    }
+
+    fn bucket_params() -> BucketParams {
+        // one general and one protected slot per channel direction
+        BucketParams {
+            congestion_slots_share: 0.0,
+            congestion_liquidity_share: 0.0,
+            protected_slots_share: 0.5,
+            protected_liquidity_share: 0.5,
+            congestion_window: Duration(0),
+        }
+    }
+
+    fn num_unendorsed_htlcs(network: &Network, from: &str, to: &str, time: &Timestamp) -> usize {
+        network.get_channel_in_direction(&node(from), &node(to)).unwrap()
+            .get_num_unendorsed_htlcs_in_flight(time)
+    }
+
+    #[test]
+    fn network_endorsement_jammer_loses_reputation() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = example_network(None, 0.0);
+        network.enable_endorsement(reputation_params());
+        network.enable_bucketing(bucket_params());
+        // an honest payment builds up Alice's reputation with Bob
+        let honest = network.create_payment(
+            &route(), Satoshi(1000), Duration(1), PaymentResult::SUCCESS);
+        assert!(network.attempt_send_payment(&honest, &route(), &Timestamp(0), &mut rng).is_ok());
+        // Alice's jam is still forwarded as endorsed, as her reputation is good
+        let jam = network.create_payment(
+            &route(), Satoshi(999), Duration(100), PaymentResult::FAILURE);
+        assert!(network.attempt_send_payment(&jam, &route(), &Timestamp(10), &mut rng).is_ok());
+        assert_eq!(num_unendorsed_htlcs(&network, "Bob", "Charlie", &Timestamp(10)), 0);
+        // once the jam resolves, holding it for 10 periods ruins Alice's reputation
+        let jam = network.create_payment(
+            &route(), Satoshi(998), Duration(100), PaymentResult::FAILURE);
+        assert!(network.attempt_send_payment(&jam, &route(), &Timestamp(150), &mut rng).is_ok());
+        let bob_tracker = network.get_reputation_tracker(&node("Bob")).unwrap();
+        assert!(!bob_tracker.has_good_reputation(&reputation_params(), &node("Alice"), &Timestamp(150)));
+        // so Bob forwards her HTLCs unendorsed
+        assert_eq!(num_unendorsed_htlcs(&network, "Alice", "Bob", &Timestamp(150)), 0);
+        assert_eq!(num_unendorsed_htlcs(&network, "Bob", "Charlie", &Timestamp(150)), 1);
+    }
+
+    #[test]
+    fn network_endorsement_resolves_htlcs_into_forwarding_nodes() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = example_network(None, 0.0);
+        network.enable_endorsement(reputation_params());
+        let payment = network.create_payment(
+            &route(), Satoshi(100), Duration(1), PaymentResult::SUCCESS);
+        assert!(network.attempt_send_payment(&payment, &route(), &Timestamp(0), &mut rng).is_ok());
+        // a later payment to Charlie only brings Bob's incoming channel direction up to date
+        let short_route = vec![node("Alice"), node("Bob"), node("Charlie")];
+        let payment = network.create_payment(
+            &short_route, Satoshi(101), Duration(1), PaymentResult::SUCCESS);
+        assert!(network.attempt_send_payment(&payment, &short_route, &Timestamp(10), &mut rng).is_ok());
+        assert_num_slots_busy(&network, [1, 2, 1]);
+        assert_eq!(network.get_reputation_tracker(&node("Bob")).unwrap().get_num_resolved_htlcs(), 1);
+    }
+
+    #[test]
+    fn network_endorsement_protected_slots() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = example_network(None, 0.0);
+        network.enable_endorsement(reputation_params());
+        network.enable_bucketing(bucket_params());
+        let honest = network.create_payment(
+            &route(), Satoshi(1000), Duration(1), PaymentResult::SUCCESS);
+        assert!(network.attempt_send_payment(&honest, &route(), &Timestamp(0), &mut rng).is_ok());
+        let jam = network.create_payment(
+            &route(), Satoshi(999), Duration(100), PaymentResult::FAILURE);
+        assert!(network.attempt_send_payment(&jam, &route(), &Timestamp(10), &mut rng).is_ok());
+        // Alice has lost her reputation: her unendorsed jams only get the general slot
+        let jam = network.create_payment(
+            &route(), Satoshi(998), Duration(100), PaymentResult::FAILURE);
+        assert!(network.attempt_send_payment(&jam, &route(), &Timestamp(150), &mut rng).is_ok());
+        let jam = network.create_payment(
+            &route(), Satoshi(997), Duration(100), PaymentResult::FAILURE);
+        let failure = network.attempt_send_payment(&jam, &route(), &Timestamp(151), &mut rng)
+            .unwrap_err();
+        assert_eq!(failure.error_type, ErrorType::NoSlots);
+        assert_eq!(failure.erring_hop, 1);
+        // Bob's own payment is endorsed and uses the protected slot
+        let bob_route = vec![node("Bob"), node("Charlie")];
+        let payment = network.create_payment(
+            &bob_route, Satoshi(1000), Duration(1), PaymentResult::SUCCESS);
+        assert!(network.attempt_send_payment(&payment, &bob_route, &Timestamp(152), &mut rng).is_ok());
+        let ch_in_dir = network.get_channel_in_direction(&node("Bob"), &node("Charlie")).unwrap();
+        assert_eq!(ch_in_dir.get_bucket_occupancy(&Bucket::Protected, &Timestamp(152)).num_htlcs, 1);
+        // Alice's last jam also used the protected bucket towards Bob, until it failed at Bob
+        assert_eq!(network.get_bucket_occupancy(&Bucket::Protected, &Timestamp(152)).num_htlcs, 1);
+        assert_eq!(network.get_num_htlcs_in_bucket(&Bucket::Protected), 2);
+    }
+
+    #[test]
+    fn network_hold_fees() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = example_network(None, 0.0);
+        // 0.1% of the amount per time unit after a grace period of 10
+        for nodes in route().windows(2) {
+            let cid = network.get_cid_in_direction(&nodes[0], &nodes[1]).unwrap().clone();
+            let ch_in_dir = network.get_channel_in_direction_mut(&cid, &nodes[0], &nodes[1]);
+            ch_in_dir.hold_fee = Some(HoldFee::new(1000, Duration(10), Satoshi(1000)));
+        }
+        // a fast payment pays no hold fees
+        let payment = network.create_payment(
+            &route(), Satoshi(1000), Duration(1), PaymentResult::SUCCESS);
+        assert!(network.attempt_send_payment(&payment, &route(), &Timestamp(0), &mut rng).is_ok());
+        // a jam held for 110 time units pays for 100 of them at every hop
+        let jam = network.create_payment(
+            &route(), Satoshi(999), Duration(110), PaymentResult::FAILURE);
+        assert!(network.attempt_send_payment(&jam, &route(), &Timestamp(0), &mut rng).is_ok());
+        network.resolve_all_htlcs();
+        // each node nets 0.1% of its hop's amount per charged time unit, the sender pays for all of them
+        let hold_revenues: Vec<i64> = route().iter()
+            .map(|node| network.get_revenue(node).hold)
+            .collect();
+        assert_eq!(hold_revenues, vec![-116 - 105 - 99, 116, 105, 99]);
+    }
+
+    #[test]
+    fn network_prepaid_hold_fees() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = example_network(None, 0.0);
+        // Charlie prices the hold fee into his upfront fee, on top of setting it for resolution
+        let hold_fee = HoldFee::new(1000, Duration(10), Satoshi(1000));
+        let cid = cid("Charlie", "Dave");
+        let ch_in_dir = network.get_channel_in_direction_mut(&cid, &node("Charlie"), &node("Dave"));
+        ch_in_dir.set_fee(FeeType::Upfront, Box::new(HoldTimeFee(hold_fee.clone())));
+        ch_in_dir.hold_fee = Some(hold_fee);
+        let jam = network.create_payment(
+            &route(), Satoshi(1000), Duration(110), PaymentResult::FAILURE);
+        assert!(network.attempt_send_payment(&jam, &route(), &Timestamp(0), &mut rng).is_ok());
+        network.resolve_all_htlcs();
+        // Dave's upfront fee is the hold fee for 100 charged time units
+        assert_eq!(network.get_revenue(&node("Dave")).upfront, 100);
+        // so it is not charged again at resolution
+        assert_eq!(network.get_revenue(&node("Dave")).hold, 0);
+        assert_eq!(network.get_revenue(&node("Alice")).hold, 0);
+    }
+
+    #[test]
+    fn network_stale_congestion_pricing() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = example_network(None, 0.0);
+        // Charlie charges ten times the upfront fee once half of his slots towards Dave are busy
+        let cid = cid("Charlie", "Dave");
+        network.get_channel_in_direction_mut(&cid, &node("Charlie"), &node("Dave")).set_fee(
+            FeeType::Upfront,
+            Box::new(CongestionFee {
+                base: Fee::new(Satoshi(2), FeeRate(0.02)),
+                curve: PricingCurve::Step { threshold: 0.5, multiplier: 10.0 },
+            }),
+        );
+        network.set_gossip_delay(Duration(10));
+        let send = |network: &mut Network, amount, time, rng: &mut StdRng| {
+            network.propagate_gossip(&Timestamp(time));
+            let payment = network.create_payment(&route(), Satoshi(amount), Duration(100), PaymentResult::SUCCESS);
+            let upfront_fee = payment.upfront_fee_satoshi;
+            (network.attempt_send_payment(&payment, &route(), &Timestamp(time), rng), upfront_fee)
+        };
+        let (result, cheap_fee) = send(&mut network, 1000, 0, &mut rng);
+        assert!(result.is_ok());
+        // the sender hasn't heard of the price increase yet
+        let (result, fee) = send(&mut network, 1000, 1, &mut rng);
+        assert_eq!(fee, cheap_fee);
+        let failure = result.unwrap_err();
+        assert_eq!(failure.error_type, ErrorType::LowFee);
+        assert_eq!(failure.erring_channel, cid);
+        assert_eq!(network.get_num_stale_fee_failures(), 1);
+        // once the update has propagated, the sender pays the higher fee
+        let (result, expensive_fee) = send(&mut network, 1000, 10, &mut rng);
+        assert!(result.is_ok());
+        assert!(expensive_fee.gt(&cheap_fee));
+        assert_eq!(network.get_num_failures(&cid, ErrorType::LowFee), 1);
+    }
+
+    #[test]
+    fn network_gossip_on_resolution() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = example_network(None, 0.0);
+        let cid = cid("Charlie", "Dave");
+        network.get_channel_in_direction_mut(&cid, &node("Charlie"), &node("Dave")).set_fee(
+            FeeType::Upfront,
+            Box::new(CongestionFee {
+                base: Fee::new(Satoshi(2), FeeRate(0.02)),
+                curve: PricingCurve::Step { threshold: 0.5, multiplier: 10.0 },
+            }),
+        );
+        network.set_gossip_delay(Duration(10));
+        let get_upfront_fee = |network: &mut Network, time| {
+            network.propagate_gossip(&Timestamp(time));
+            network.create_payment(&route(), Satoshi(1000), Duration(5), PaymentResult::SUCCESS).upfront_fee_satoshi
+        };
+        let cheap_fee = get_upfront_fee(&mut network, 0);
+        let payment = network.create_payment(&route(), Satoshi(1000), Duration(5), PaymentResult::SUCCESS);
+        assert!(network.attempt_send_payment(&payment, &route(), &Timestamp(0), &mut rng).is_ok());
+        // the HTLC added at 0 is gossiped at 10, its resolution at 5 is gossiped at 15
+        assert!(get_upfront_fee(&mut network, 12).gt(&cheap_fee));
+        assert_eq!(get_upfront_fee(&mut network, 15), cheap_fee);
+    }
+
+    #[test]
+    fn network_inbound_fees() {
+        let mut network = example_network(None, 0.0);
+        let payment = network.create_payment(
+            &route(), Satoshi(1000), Duration(1), PaymentResult::SUCCESS);
+        let hop_payments: Vec<&Payment> = std::iter::successors(Some(&payment), |p| p.get_downstream_payment())
+            .collect();
+        let charlie_upfront_fee = Network::get_hop_upfront_fee(&hop_payments, 1);
+        // Charlie waives his upfront fee for HTLCs coming from Bob
+        let cid = cid("Bob", "Charlie");
+        let ch_in_dir = network.get_channel_in_direction_mut(&cid, &node("Bob"), &node("Charlie"));
+        ch_in_dir.inbound_upfront_fee = Some(InboundFee::new(-1_000, FeeRate(0.0)));
+        let discounted_payment = network.create_payment(
+            &route(), Satoshi(1000), Duration(1), PaymentResult::SUCCESS);
+        assert_eq!(discounted_payment.upfront_fee_satoshi, payment.upfront_fee_satoshi.sub(&charlie_upfront_fee));
+        assert_eq!(discounted_payment.success_fee_satoshi, payment.success_fee_satoshi);
+        // a surcharge on success fees raises the amount forwarded by Bob
+        let ch_in_dir = network.get_channel_in_direction_mut(&cid, &node("Bob"), &node("Charlie"));
+        ch_in_dir.inbound_success_fee = Some(InboundFee::new(10, FeeRate(0.0)));
+        let surcharged_payment = network.create_payment(
+            &route(), Satoshi(1000), Duration(1), PaymentResult::SUCCESS);
+        assert!(surcharged_payment.success_fee_satoshi.gt(&payment.success_fee_satoshi));
+    }
+
+    #[test]
+    fn network_inbound_fee_on_last_hop() {
+        let mut network = example_network(None, 0.0);
+        let payment = network.create_payment(
+            &route(), Satoshi(1000), Duration(1), PaymentResult::SUCCESS);
+        let dave_upfront_fee = payment.get_downstream_payment().unwrap()
+            .get_downstream_payment().unwrap().upfront_fee_satoshi;
+        assert_eq!(dave_upfront_fee, Satoshi(22));
+        // Dave gives a discount for HTLCs coming in from Charlie, with a negative base fee
+        let cid = cid("Charlie", "Dave");
+        let ch_in_dir = network.get_channel_in_direction_mut(&cid, &node("Charlie"), &node("Dave"));
+        ch_in_dir.inbound_upfront_fee = Some(InboundFee::new(-7, FeeRate(0.002)));
+        let discounted_payment = network.create_payment(
+            &route(), Satoshi(1000), Duration(1), PaymentResult::SUCCESS);
+        let last_hop_payment = discounted_payment.get_downstream_payment().unwrap()
+            .get_downstream_payment().unwrap();
+        assert_eq!(last_hop_payment.upfront_fee_satoshi, Satoshi(17));
+        assert_eq!(last_hop_payment.get_amount(), Satoshi(1000));
+        // the sender pays less in total
+        assert!(discounted_payment.upfront_fee_satoshi.lt(&payment.upfront_fee_satoshi));
+    }
+
+    #[test]
+    fn network_reverse_hold_fees() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = example_network(None, 0.0);
+        // every node charges its downstream peer 0.1% of the amount per time unit after a grace period of 10
+        for nodes in route().windows(2) {
+            let cid = network.get_cid_in_direction(&nodes[0], &nodes[1]).unwrap().clone();
+            let ch_in_dir = network.get_channel_in_direction_mut(&cid, &nodes[0], &nodes[1]);
+            ch_in_dir.reverse_hold_fee = Some(HoldFee::new(1000, Duration(10), Satoshi(1000)));
+        }
+        // the receiver holds the HTLCs for 110 time units
+        let jam = network.create_payment(
+            &route(), Satoshi(999), Duration(110), PaymentResult::FAILURE);
+        assert!(network.attempt_send_payment(&jam, &route(), &Timestamp(0), &mut rng).is_ok());
+        network.resolve_all_htlcs();
+        // each upstream node is paid for its own hop, and the receiver pays for all of them
+        let reverse_hold_revenues: Vec<i64> = route().iter()
+            .map(|node| network.get_revenue(node).reverse_hold)
+            .collect();
+        assert_eq!(reverse_hold_revenues, vec![116, 105, 99, -116 - 105 - 99]);
+        assert!(route().iter().all(|node| network.get_revenue(node).hold == 0));
+    }
+}
@@ -0,0 +1,15 @@
+use crate::channel::htlc::Htlc as Htlc;
+use crate::common::{duration::Duration, fee::FeeType, nodeid::NodeId, satoshi::Satoshi};
+
+use super::Network as Network;
+
+impl Network {
+    pub(super) fn refund_upfront_fee(&mut self, upstream: &NodeId, downstream: &NodeId, htlc: &Htlc, hold_time: &Duration) {
+        // The upfront fee is cumulative, so each node refunds what it has received and gets refunded
+        // what it has paid: a node keeps the non-refunded share of its own fee.
+        let refund = htlc.upfront_fee.mul(self.upfront_fee_policy.get_refund_share(hold_time));
+        if refund.gt(&Satoshi(0)) {
+            self.refund_fee(FeeType::Upfront, downstream, upstream, &refund, htlc);
+        }
+    }
+}
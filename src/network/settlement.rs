@@ -0,0 +1,146 @@
+use crate::channel::direction::Direction as Direction;
+use crate::channel::htlc::Htlc as Htlc;
+use crate::common::{
+    channelid::ChannelId,
+    duration::Duration,
+    fee::FeeType,
+    holdfee::HoldFee,
+    nodeid::NodeId,
+    paymentid::PaymentId,
+    paymentresult::PaymentResult,
+    satoshi::Satoshi,
+    timestamp::Timestamp,
+};
+use crate::node::behavior::HtlcInfo as HtlcInfo;
+
+use super::Network as Network;
+
+impl Network {
+    pub fn resolve_payment_no_earlier_than(&mut self, payment_id: &PaymentId, route: &[NodeId], resolution_time: &Timestamp) {
+        // The receiver resolves a payment that has reached it no earlier than the given time
+        // (e.g., once all MPP parts have arrived), and the resolution travels back hop by hop.
+        // HTLCs that resolve later anyway (e.g., held by the receiver) are left as is.
+        for nodes in route.windows(2) {
+            let (upstream, downstream) = (&nodes[0], &nodes[1]);
+            // the sender may have picked any of the channels between the nodes
+            let cids: Vec<ChannelId> = self.get_cids_in_direction(upstream, downstream).cloned().collect();
+            for cid in cids {
+                let ch_in_dir = self.get_channel_in_direction_mut(&cid, upstream, downstream);
+                let htlcs: Vec<(Htlc, Timestamp)> = ch_in_dir.get_htlcs()
+                    .filter(|(htlc, _)| &htlc.payment_id == payment_id)
+                    .map(|(htlc, htlc_resolution_time)| (htlc.clone(), htlc_resolution_time.clone()))
+                    .collect();
+                for (htlc, htlc_resolution_time) in htlcs {
+                    let new_resolution_time = resolution_time.add(&htlc.resolution_delay);
+                    if new_resolution_time > htlc_resolution_time {
+                        ch_in_dir.postpone_htlc(&htlc, new_resolution_time);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn resolve_all_htlcs(&mut self) {
+        // at the end of the simulation, resolve the HTLCs that are still in flight
+        self.resolve_htlcs(None);
+    }
+
+    pub(super) fn resolve_htlcs(&mut self, up_to_time: Option<&Timestamp>) {
+        // resolve in-flight HTLCs with resolution times up to the given time (or all of them)
+        let mut cids: Vec<ChannelId> = self.channels.keys().cloned().collect();
+        cids.sort();
+        for cid in cids {
+            let (node_a, node_b) = self.channel_ends[&cid].clone();
+            for (from, to) in [(&node_a, &node_b), (&node_b, &node_a)] {
+                self.resolve_htlcs_in_direction(&cid, from, to, up_to_time);
+            }
+        }
+    }
+
+    pub(super) fn resolve_htlcs_into(&mut self, nodes: &[NodeId], time: &Timestamp) {
+        // A node's reputation tracker only learns from HTLCs resolved on its incoming channel directions:
+        // bring those up to date for the given nodes, leaving the rest of the network alone.
+        let mut directions: Vec<(ChannelId, NodeId, NodeId)> = Vec::new();
+        for node in nodes {
+            for peer in self.get_neighbors(node) {
+                for cid in self.get_cids_in_direction(peer, node) {
+                    let direction = (cid.clone(), peer.clone(), node.clone());
+                    if !directions.contains(&direction) {
+                        directions.push(direction);
+                    }
+                }
+            }
+        }
+        for (cid, from, to) in directions {
+            self.resolve_htlcs_in_direction(&cid, &from, &to, Some(time));
+        }
+    }
+
+    fn resolve_htlcs_in_direction(&mut self, cid: &ChannelId, from: &NodeId, to: &NodeId, up_to_time: Option<&Timestamp>) {
+        let direction = Direction::new(from, to);
+        let mut released_htlcs = Vec::new();
+        if let Some(ch_in_dir) = self.channels.get_mut(cid).unwrap().get_channel_in_direction_mut(&direction) {
+            while !ch_in_dir.all_slots_free()
+                && up_to_time.is_none_or(|time| ch_in_dir.get_earliest_htlc_resolution_time() <= time)
+            {
+                released_htlcs.push(ch_in_dir.pop_htlc());
+            }
+        }
+        // the channel direction announces its lower occupancy once the last of the HTLCs has resolved
+        let announce_time = released_htlcs.last().map(|(_, resolution_time)| resolution_time.clone());
+        for (htlc, resolution_time) in released_htlcs {
+            self.resolve_htlc(cid, from, to, htlc, &resolution_time);
+        }
+        if let Some(announce_time) = announce_time {
+            self.announce_direction_occupancy(cid, from, to, &announce_time);
+        }
+    }
+
+    pub(super) fn resolve_htlc(
+        &mut self,
+        cid: &ChannelId,
+        upstream: &NodeId,
+        downstream: &NodeId,
+        htlc: Htlc,
+        resolution_time: &Timestamp,
+    ) {
+        // the amount moves to the downstream side and the success fee is paid only if the payment succeeds
+        let succeeded = htlc.desired_result == PaymentResult::SUCCESS;
+        if succeeded {
+            self.channels.get_mut(cid).unwrap().settle(Direction::new(upstream, downstream), &htlc.amount);
+            self.transfer_fee(FeeType::Success, upstream, downstream, &htlc.success_fee_satoshi, &htlc);
+        }
+        // hold fees are paid either way, for this hop and (passed on) for all downstream hops
+        let hold_time = Duration(resolution_time.0.saturating_sub(htlc.added_at.0));
+        let get_total_hold_fee = |hold_fees: &[(HoldFee, Satoshi)]| hold_fees
+            .iter()
+            .fold(Satoshi(0), |total, (hold_fee, amount)| total.add(&hold_fee.apply(amount, &hold_time)));
+        let hold_fee = get_total_hold_fee(&htlc.hold_fees);
+        if hold_fee.gt(&Satoshi(0)) {
+            self.transfer_fee(FeeType::Hold, upstream, downstream, &hold_fee, &htlc);
+        }
+        // Reverse hold fees flow the other way: each node passes on what it owes for all upstream hops,
+        // so the node that actually holds the HTLC (e.g., a delaying receiver) ends up paying all of them.
+        let reverse_hold_fee = get_total_hold_fee(&htlc.reverse_hold_fees);
+        if reverse_hold_fee.gt(&Satoshi(0)) {
+            self.transfer_fee(FeeType::ReverseHold, downstream, upstream, &reverse_hold_fee, &htlc);
+        }
+        self.refund_upfront_fee(upstream, downstream, &htlc, &hold_time);
+        self.release_htlc_lock(upstream, downstream, &htlc, resolution_time);
+        let htlc_info = HtlcInfo {
+            payment_id: htlc.payment_id.clone(),
+            peer: upstream.clone(),
+            amount: htlc.amount,
+            time: resolution_time.clone(),
+        };
+        self.get_node_mut(downstream).behavior.on_resolve(&htlc_info, &htlc.desired_result, &hold_time);
+        // the downstream node updates the reputation of its upstream peer
+        if let Some(reputation_params) = &self.reputation_params {
+            let fee = if succeeded { htlc.forwarding_fee } else { Satoshi(0) };
+            self.reputations
+                .entry(downstream.clone())
+                .or_default()
+                .record_resolution(reputation_params, upstream, fee, &hold_time, htlc.endorsed, resolution_time);
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use std::fmt::Debug;
+
+use rand::{Rng, RngCore};
+
+use crate::common::{
+    duration::Duration,
+    errortype::ErrorType,
+    nodeid::NodeId,
+    paymentid::PaymentId,
+    paymentresult::PaymentResult,
+    satoshi::Satoshi,
+    timestamp::Timestamp,
+};
+
+// What a node sees of an HTLC: the payment it belongs to, the peer on the other end, and when.
+#[derive(Debug, Clone)]
+pub struct HtlcInfo {
+    pub payment_id: PaymentId,
+    pub peer: NodeId,
+    pub amount: Satoshi,
+    pub time: Timestamp,
+}
+
+// How the receiver resolves a payment: after how long, and whether it settles or fails it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolution {
+    pub hold_time: Duration,
+    pub result: PaymentResult,
+}
+
+// What a node does with HTLCs, invoked by the network at each step of forwarding.
+// Every method has an honest default, so behaviors only override what they change.
+// Attack and defence strategies are written as behaviors, not as branches in the simulation.
+pub trait NodeBehavior: Debug {
+    // an HTLC from the peer has been added to the node's incoming channel
+    fn on_add(&mut self, _htlc: &HtlcInfo) {}
+
+    // the node is about to forward an HTLC to the peer: an error fails it instead
+    fn on_forward(&mut self, _htlc: &HtlcInfo, _rng: &mut dyn RngCore) -> Result<(), ErrorType> {
+        Ok(())
+    }
+
+    // the payment has reached the node as its receiver:
+    // the sender asks for the given resolution, the node may decide otherwise
+    fn on_hold(&mut self, _htlc: &HtlcInfo, requested: Resolution) -> Resolution {
+        requested
+    }
+
+    // an HTLC from the peer has been resolved after the given hold time
+    fn on_resolve(&mut self, _htlc: &HtlcInfo, _result: &PaymentResult, _hold_time: &Duration) {}
+}
+
+// Forwards and resolves HTLCs as asked.
+#[derive(Debug, Clone, Default)]
+pub struct HonestRouter;
+
+impl NodeBehavior for HonestRouter {}
+
+// Settles payments, but only after holding them for (at least) the given time,
+// as with hold invoices.
+#[derive(Debug, Clone)]
+pub struct HoldingReceiver {
+    pub hold_time: Duration,
+}
+
+impl NodeBehavior for HoldingReceiver {
+    fn on_hold(&mut self, _htlc: &HtlcInfo, requested: Resolution) -> Resolution {
+        Resolution {
+            hold_time: Duration(requested.hold_time.0.max(self.hold_time.0)),
+            ..requested
+        }
+    }
+}
+
+// Receives payments from its own (colluding) senders, holds them for as long as it can get away with,
+// and then fails them, so that the sender pays no success fees.
+#[derive(Debug, Clone)]
+pub struct Jammer {
+    pub hold_time: Duration,
+}
+
+impl NodeBehavior for Jammer {
+    fn on_hold(&mut self, _htlc: &HtlcInfo, _requested: Resolution) -> Resolution {
+        Resolution {
+            hold_time: self.hold_time.clone(),
+            result: PaymentResult::FAILURE,
+        }
+    }
+}
+
+// Fails the HTLCs it should forward with the given probability,
+// reporting the given error type instead of the true reason.
+#[derive(Debug, Clone)]
+pub struct DeliberateFailer {
+    pub fail_prob: f64,
+    pub spoofing_error_type: ErrorType,
+}
+
+impl DeliberateFailer {
+    pub fn new(fail_prob: f64, spoofing_error_type: Option<ErrorType>) -> Self {
+        assert!((0.0..=1.0).contains(&fail_prob),
+        "Deliberate failure probability must be in [0, 1], got {}", fail_prob);
+        DeliberateFailer {
+            fail_prob,
+            spoofing_error_type: spoofing_error_type.unwrap_or(ErrorType::FailedDeliberately),
+        }
+    }
+}
+
+impl NodeBehavior for DeliberateFailer {
+    fn on_forward(&mut self, _htlc: &HtlcInfo, rng: &mut dyn RngCore) -> Result<(), ErrorType> {
+        if rng.gen_bool(self.fail_prob) {
+            Err(self.spoofing_error_type)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::node;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn htlc() -> HtlcInfo {
+        HtlcInfo {
+            payment_id: PaymentId::new(),
+            peer: node("Alice"),
+            amount: Satoshi(100),
+            time: Timestamp(0),
+        }
+    }
+
+    fn requested() -> Resolution {
+        Resolution { hold_time: Duration(5), result: PaymentResult::SUCCESS }
+    }
+
+    #[test]
+    fn behavior_on_hold() {
+        assert_eq!(HonestRouter.on_hold(&htlc(), requested()), requested());
+        let mut holding_receiver = HoldingReceiver { hold_time: Duration(100) };
+        assert_eq!(
+            holding_receiver.on_hold(&htlc(), requested()),
+            Resolution { hold_time: Duration(100), result: PaymentResult::SUCCESS },
+        );
+        let mut jammer = Jammer { hold_time: Duration(1000) };
+        assert_eq!(
+            jammer.on_hold(&htlc(), requested()),
+            Resolution { hold_time: Duration(1000), result: PaymentResult::FAILURE },
+        );
+    }
+
+    #[test]
+    fn behavior_deliberate_failer() {
+        let mut rng = StdRng::seed_from_u64(0);
+        assert!(HonestRouter.on_forward(&htlc(), &mut rng).is_ok());
+        let mut failer = DeliberateFailer::new(1.0, Some(ErrorType::NoSlots));
+        assert_eq!(failer.on_forward(&htlc(), &mut rng), Err(ErrorType::NoSlots));
+        let mut failer = DeliberateFailer::new(0.0, None);
+        assert!(failer.on_forward(&htlc(), &mut rng).is_ok());
+    }
+}
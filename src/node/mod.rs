@@ -0,0 +1,139 @@
+pub mod behavior;
+
+use std::collections::BTreeMap;
+
+use crate::channel::circuitbreaker::{CircuitBreaker as CircuitBreaker, CircuitBreakerParams as CircuitBreakerParams};
+use crate::common::{
+    duration::Duration,
+    errortype::ErrorType,
+    nodeid::NodeId,
+    revenue::Revenue,
+    satoshi::Satoshi,
+    timestamp::Timestamp,
+};
+
+use behavior::{HonestRouter as HonestRouter, NodeBehavior as NodeBehavior};
+
+// A node of the network: its wallet, the fees it has earned and paid, and how it treats HTLCs.
+#[derive(Debug)]
+pub struct Node {
+    id: NodeId,
+    // what the node holds before paying or earning any fees
+    wallet: Satoshi,
+    revenue: Revenue,
+    // the amount times the time it has been locked in the node's outgoing HTLCs, once they are resolved
+    capital_locked: u64,
+    pub behavior: Box<dyn NodeBehavior>,
+    // if set, the node runs a circuit breaker on HTLCs from each of its peers, over all channels with the peer
+    circuit_breaker_params: Option<CircuitBreakerParams>,
+    circuit_breakers: BTreeMap<NodeId, CircuitBreaker>,
+}
+
+impl Node {
+    pub fn new(id: NodeId, wallet: Option<Satoshi>, behavior: Option<Box<dyn NodeBehavior>>) -> Self {
+        Node {
+            id,
+            wallet: wallet.unwrap_or(Satoshi(0)),
+            revenue: Revenue::default(),
+            capital_locked: 0,
+            behavior: behavior.unwrap_or(Box::new(HonestRouter)),
+            circuit_breaker_params: None,
+            circuit_breakers: BTreeMap::new(),
+        }
+    }
+
+    pub fn get_id(&self) -> &NodeId {
+        &self.id
+    }
+
+    pub fn get_wallet(&self) -> &Satoshi {
+        &self.wallet
+    }
+
+    pub fn get_revenue(&self) -> &Revenue {
+        &self.revenue
+    }
+
+    pub fn get_revenue_mut(&mut self) -> &mut Revenue {
+        &mut self.revenue
+    }
+
+    pub fn get_capital_locked(&self) -> u64 {
+        self.capital_locked
+    }
+
+    pub fn lock_capital(&mut self, amount: &Satoshi, hold_time: &Duration) {
+        self.capital_locked += amount.0 * hold_time.0;
+    }
+
+    pub fn set_circuit_breaker(&mut self, circuit_breaker_params: CircuitBreakerParams) {
+        self.circuit_breaker_params = Some(circuit_breaker_params);
+        self.circuit_breakers.clear();
+    }
+
+    pub fn get_circuit_breaker(&self, peer: &NodeId) -> Option<&CircuitBreaker> {
+        self.circuit_breakers.get(peer)
+    }
+
+    pub fn admit_htlc<'a>(
+        &mut self,
+        peer: &NodeId,
+        time: &Timestamp,
+        pending_resolution_times: impl Iterator<Item = &'a Timestamp>,
+    ) -> Result<Duration, ErrorType> {
+        // Decide on a new HTLC from the peer, given when the peer's pending HTLCs resolve.
+        // Return how long the HTLC has to wait in the queue (zero without a circuit breaker).
+        let Some(circuit_breaker_params) = &self.circuit_breaker_params else {
+            return Ok(Duration(0));
+        };
+        let pending_resolution_times: Vec<Timestamp> = pending_resolution_times.cloned().collect();
+        self.circuit_breakers
+            .entry(peer.clone())
+            .or_insert_with(|| CircuitBreaker::new(circuit_breaker_params.clone()))
+            .admit(time, &pending_resolution_times)
+    }
+
+    pub fn get_balance(&self) -> i64 {
+        // the wallet plus all fees earned, minus all fees paid
+        self.wallet.0 as i64 + self.revenue.total()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel::circuitbreaker::CircuitBreakerMode;
+    use crate::common::fee::FeeType;
+    use crate::testutils;
+
+    #[test]
+    fn node_balance() {
+        let mut node = Node::new(testutils::node("Alice"), Some(Satoshi(1000)), None);
+        node.get_revenue_mut().earn(FeeType::Success, &Satoshi(10));
+        node.get_revenue_mut().spend(FeeType::Upfront, &Satoshi(30));
+        assert_eq!(node.get_balance(), 980);
+        assert_eq!(node.get_wallet(), &Satoshi(1000));
+        node.lock_capital(&Satoshi(100), &Duration(5));
+        node.lock_capital(&Satoshi(10), &Duration(2));
+        assert_eq!(node.get_capital_locked(), 520);
+    }
+
+    #[test]
+    fn node_circuit_breaker_per_peer() {
+        let mut node = Node::new(testutils::node("Bob"), None, None);
+        let (alice, charlie) = (testutils::node("Alice"), testutils::node("Charlie"));
+        // without a circuit breaker, everything passes
+        assert_eq!(node.admit_htlc(&alice, &Timestamp(0), [Timestamp(10)].iter()), Ok(Duration(0)));
+        node.set_circuit_breaker(CircuitBreakerParams {
+            max_pending_htlcs: 1,
+            burst: 10,
+            rate: 1.0,
+            mode: CircuitBreakerMode::Fail,
+        });
+        assert_eq!(node.admit_htlc(&alice, &Timestamp(0), [Timestamp(10)].iter()), Err(ErrorType::RateLimited));
+        // each peer has its own limits
+        assert_eq!(node.admit_htlc(&charlie, &Timestamp(0), [].iter()), Ok(Duration(0)));
+        assert_eq!(node.get_circuit_breaker(&alice).unwrap().get_num_dropped(), 1);
+        assert_eq!(node.get_circuit_breaker(&charlie).unwrap().get_num_dropped(), 0);
+    }
+}
@@ -1,7 +1,9 @@
 use crate::common::{
     nodeid::NodeId,
-    fee::Fee,
+    feepolicy::{FeeContext, FeePolicy, Occupancy},
+    holdfee::HoldFee,
     paymentresult::PaymentResult,
+    trafficclass::TrafficClass,
     duration::Duration,
     satoshi::Satoshi};
 
@@ -12,6 +14,12 @@ pub struct Payment{
     pub desired_result: PaymentResult,
     pub processing_delay: Duration,
     pub body: Satoshi,
+    // the hold fee terms for this hop, if any (the fee itself is only known at resolution)
+    pub hold_fee: Option<HoldFee>,
+    // the reverse hold fee terms for this hop, if any, paid by the downstream node
+    pub reverse_hold_fee: Option<HoldFee>,
+    // the kind of traffic the payment belongs to, the same for all hops
+    pub traffic_class: TrafficClass,
     downstream_node: Option<NodeId>,
     downstream_payment: Option<Box<Payment>>,
 }
@@ -21,10 +29,12 @@ pub struct Payment{
 // Let's simply provide fee functions as taken from channelindirection by the caller
 
 impl Payment {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        // TODO: use references? Requires lifetimes
-        upfront_fee: Fee,
-        success_fee: Fee,
+        upfront_fee: &dyn FeePolicy,
+        success_fee: &dyn FeePolicy,
+        // the occupancy of the channel direction as known to the sender, for congestion-priced fees
+        occupancy: Option<Occupancy>,
         desired_result: Option<PaymentResult>,
         processing_delay: Option<Duration>,
         body: Option<Satoshi>,
@@ -48,33 +58,50 @@ impl Payment {
             downstream_payment.is_some();
         // make sure we don't mix those two cases
         assert!(is_last_hop ^ is_not_last_hop);
+        let occupancy = occupancy.unwrap_or_default();
         if is_last_hop {
+            // the receiver charges no success fee, but the upfront fee may depend on the nominal one
+            let body = body.unwrap();
+            let processing_delay = processing_delay.unwrap();
+            let success_fee_context = FeeContext { hold_time: processing_delay.clone(), occupancy, ..FeeContext::new(body) };
+            let upfront_fee_context = FeeContext {
+                success_fee: success_fee.get_fee(&success_fee_context),
+                ..success_fee_context
+            };
             Self {
-                    upfront_fee_satoshi: upfront_fee.apply(&body.unwrap()),
+                    upfront_fee_satoshi: upfront_fee.get_fee(&upfront_fee_context),
                     success_fee_satoshi: Satoshi(0),
                     desired_result: desired_result.unwrap(),
-                    processing_delay: processing_delay.unwrap(),
-                    body: body.unwrap(),
+                    processing_delay,
+                    body,
+                    hold_fee: None,
+                    reverse_hold_fee: None,
+                    traffic_class: TrafficClass::default(),
                     downstream_node,
                     downstream_payment,
                 }
         } else {
             let dp = downstream_payment.unwrap();
             let dn = downstream_node.unwrap();
-            let amount = &dp.body.add(
-                &success_fee.apply(&dp.body)
+            let fee_context = |amount| FeeContext {
+                hold_time: dp.processing_delay.clone(),
+                occupancy,
+                ..FeeContext::new(amount)
+            };
+            let hop_success_fee = success_fee.get_fee(&fee_context(dp.body.add(&dp.success_fee_satoshi)));
+            let amount = dp.body.add(
+                &success_fee.get_fee(&fee_context(dp.body))
             );
+            let upfront_fee_context = FeeContext { success_fee: hop_success_fee, ..fee_context(amount) };
             Self {
-                upfront_fee_satoshi: upfront_fee.apply(&amount).add(&dp.upfront_fee_satoshi),
-                success_fee_satoshi: (
-                    success_fee.apply(
-                        &dp.body.add(&dp.success_fee_satoshi)
-                    ).add( 
-                    &dp.success_fee_satoshi)
-                ),
+                upfront_fee_satoshi: upfront_fee.get_fee(&upfront_fee_context).add(&dp.upfront_fee_satoshi),
+                success_fee_satoshi: hop_success_fee.add(&dp.success_fee_satoshi),
                 desired_result: dp.desired_result.clone(),
                 processing_delay: dp.processing_delay.clone(),
                 body: dp.get_amount(),
+                hold_fee: None,
+                reverse_hold_fee: None,
+                traffic_class: dp.traffic_class,
                 downstream_node: Some(dn),
                 downstream_payment: Some(dp.clone()),
             }
@@ -83,12 +110,22 @@ impl Payment {
     pub fn get_amount(&self) -> Satoshi {
         self.body.add(&self.success_fee_satoshi)
     }
+    pub fn get_downstream_payment(&self) -> Option<&Payment> {
+        self.downstream_payment.as_deref()
+    }
+    pub fn set_traffic_class(&mut self, traffic_class: TrafficClass) {
+        self.traffic_class = traffic_class;
+        if let Some(dp) = self.downstream_payment.as_mut() {
+            dp.set_traffic_class(traffic_class);
+        }
+    }
 }
 
 
 #[cfg(test)]
 mod tests {
-    use crate::common::fee::FeeRate;
+    use crate::common::fee::{Fee, FeeRate};
+    use crate::common::feepolicy::{CongestionFee, PricingCurve, SuccessProportionalFee};
 
     use super::*;
 
@@ -97,9 +134,9 @@ mod tests {
         let example_upfront_fee = Fee::new(Satoshi(2), FeeRate(0.02));
         let example_success_fee = Fee::new(Satoshi(5), FeeRate(0.05));
         let p_cd = Payment::new(
-            // cloning as a temporary measure while I figure out lifetimes
-            example_upfront_fee.clone(),
-            example_success_fee.clone(),
+            &example_upfront_fee,
+            &example_success_fee,
+            None,
             Some(PaymentResult::SUCCESS),
             Some(Duration(1)),
             Some(Satoshi(100)),
@@ -107,8 +144,9 @@ mod tests {
             None,
         );
         let p_bc = Payment::new(
-            example_upfront_fee.clone(),
-            example_success_fee.clone(),
+            &example_upfront_fee,
+            &example_success_fee,
+            None,
             None,
             None,
             None,
@@ -116,8 +154,9 @@ mod tests {
             Some(Box::new(p_cd)),
         );
         let p_ab = Payment::new(
-            example_upfront_fee.clone(),
-            example_success_fee.clone(),
+            &example_upfront_fee,
+            &example_success_fee,
+            None,
             None,
             None,
             None,
@@ -144,4 +183,48 @@ mod tests {
         assert_eq!(p_cd.downstream_node, None);
     }
 
+    #[test]
+    fn payment_fee_policies() {
+        // the upfront fee is half the success fee of the same hop, and is charged on the last hop too
+        let upfront_fee = SuccessProportionalFee(0.5);
+        let success_fee = Fee::new(Satoshi(5), FeeRate(0.05));
+        let p_bc = Payment::new(
+            &upfront_fee,
+            &success_fee,
+            None,
+            Some(PaymentResult::SUCCESS),
+            Some(Duration(1)),
+            Some(Satoshi(100)),
+            None,
+            None,
+        );
+        assert_eq!(p_bc.success_fee_satoshi, Satoshi(0));
+        assert_eq!(p_bc.upfront_fee_satoshi, Satoshi(5));
+        let p_ab = Payment::new(
+            &upfront_fee,
+            &success_fee,
+            None,
+            None,
+            None,
+            None,
+            Some(NodeId(String::from("Charlie"))),
+            Some(Box::new(p_bc)),
+        );
+        assert_eq!(p_ab.success_fee_satoshi, Satoshi(10));
+        assert_eq!(p_ab.upfront_fee_satoshi, Satoshi(10));
+        // congestion-priced upfront fees depend on the occupancy passed in
+        let upfront_fee = CongestionFee { base: Fee::new(Satoshi(4), FeeRate(0.0)), curve: PricingCurve::Linear(1.0) };
+        let p = Payment::new(
+            &upfront_fee,
+            &success_fee,
+            Some(Occupancy { slots: 0.5, liquidity: 0.0 }),
+            Some(PaymentResult::SUCCESS),
+            Some(Duration(1)),
+            Some(Satoshi(100)),
+            None,
+            None,
+        );
+        assert_eq!(p.upfront_fee_satoshi, Satoshi(6));
+    }
+
 }
\ No newline at end of file
@@ -0,0 +1,193 @@
+use std::collections::VecDeque;
+
+use crate::common::{
+    duration::Duration,
+    nodeid::NodeId,
+    satoshi::Satoshi,
+    timestamp::Timestamp,
+};
+
+// Local reputation as in the HTLC endorsement proposal:
+// https://github.com/lightning/bolts/pull/1071
+// Each node tracks, for each of its upstream peers, how much revenue the peer has contributed
+// versus how long its endorsed HTLCs have held the node's resources.
+// A peer has good reputation if its contribution over the reputation window
+// is at least the node's total revenue over the (shorter) revenue window.
+// The node only forwards HTLCs as endorsed if they come endorsed from a peer with good reputation,
+// and only endorsed HTLCs may use the protected bucket (see channel::bucket).
+
+#[derive(Debug, Clone)]
+pub struct ReputationParams {
+    // the node's own revenue is counted over this window
+    pub revenue_window: Duration,
+    // the peer's reputation is counted over revenue_window * reputation_multiplier
+    pub reputation_multiplier: u64,
+    // endorsed HTLCs held longer than this are charged for each extra period
+    pub resolution_period: Duration,
+}
+
+impl Default for ReputationParams {
+    fn default() -> Self {
+        // the proposal suggests two weeks of revenue, 12x that for reputation, and 90s to resolve
+        ReputationParams {
+            revenue_window: Duration(14 * 24 * 60 * 60),
+            reputation_multiplier: 12,
+            resolution_period: Duration(90),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ResolvedHtlc {
+    time: Timestamp,
+    peer: NodeId,
+    fee: Satoshi,
+    contribution: i64,
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct ReputationTracker {
+    // oldest first; HTLCs that have fallen out of both windows are dropped
+    resolved_htlcs: VecDeque<ResolvedHtlc>,
+}
+
+impl ReputationTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_revenue(&self, params: &ReputationParams, time: &Timestamp) -> i64 {
+        // the node's revenue from all its peers over the revenue window
+        self.resolved_htlcs
+            .iter()
+            .filter(|resolved| Self::is_within_window(&resolved.time, time, &params.revenue_window))
+            .map(|resolved| resolved.fee.0 as i64)
+            .sum()
+    }
+
+    pub fn get_reputation(&self, params: &ReputationParams, peer: &NodeId, time: &Timestamp) -> i64 {
+        let reputation_window = Self::get_reputation_window(params);
+        self.resolved_htlcs
+            .iter()
+            .filter(|resolved| &resolved.peer == peer)
+            .filter(|resolved| Self::is_within_window(&resolved.time, time, &reputation_window))
+            .map(|resolved| resolved.contribution)
+            .sum()
+    }
+
+    pub fn has_good_reputation(&self, params: &ReputationParams, peer: &NodeId, time: &Timestamp) -> bool {
+        self.get_reputation(params, peer, time) >= self.get_revenue(params, time)
+    }
+
+    pub fn record_resolution(
+        &mut self,
+        params: &ReputationParams,
+        peer: &NodeId,
+        fee: Satoshi,
+        hold_time: &Duration,
+        endorsed: bool,
+        time: &Timestamp,
+    ) {
+        // Unendorsed HTLCs can only use general slots, so holding them costs nothing to reputation.
+        // Endorsed HTLCs are charged the node's average revenue per resolution period
+        // for every period they were held beyond the first one.
+        let mut contribution = fee.0 as i64;
+        if endorsed && params.resolution_period.0 > 0 {
+            let num_extra_periods = hold_time.0.div_ceil(params.resolution_period.0).saturating_sub(1);
+            let num_periods_in_window = std::cmp::max(1, params.revenue_window.0 / params.resolution_period.0);
+            let revenue_per_period = self.get_revenue(params, time) / num_periods_in_window as i64;
+            contribution -= revenue_per_period * num_extra_periods as i64;
+        }
+        self.resolved_htlcs.push_back(ResolvedHtlc {
+            time: time.clone(),
+            peer: peer.clone(),
+            fee,
+            contribution,
+        });
+        self.prune(params, time);
+    }
+
+    pub fn get_num_resolved_htlcs(&self) -> usize {
+        self.resolved_htlcs.len()
+    }
+
+    fn prune(&mut self, params: &ReputationParams, time: &Timestamp) {
+        // HTLCs are recorded roughly in the order of their resolution,
+        // so those that no window will count again are at the front
+        let window = std::cmp::max(params.revenue_window.0, Self::get_reputation_window(params).0);
+        while self.resolved_htlcs.front().is_some_and(|resolved| resolved.time.0 + window <= time.0) {
+            self.resolved_htlcs.pop_front();
+        }
+    }
+
+    fn get_reputation_window(params: &ReputationParams) -> Duration {
+        Duration(params.revenue_window.0 * params.reputation_multiplier)
+    }
+
+    fn is_within_window(event_time: &Timestamp, time: &Timestamp, window: &Duration) -> bool {
+        event_time <= time && time.0 - event_time.0 < window.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::node;
+
+    fn params() -> ReputationParams {
+        ReputationParams {
+            revenue_window: Duration(100),
+            reputation_multiplier: 10,
+            resolution_period: Duration(10),
+        }
+    }
+
+    #[test]
+    fn reputation_new_peer_with_no_revenue() {
+        // with no revenue to protect, every peer has good reputation
+        let tracker = ReputationTracker::new();
+        assert!(tracker.has_good_reputation(&params(), &node("Alice"), &Timestamp(0)));
+    }
+
+    #[test]
+    fn reputation_windows() {
+        let mut tracker = ReputationTracker::new();
+        // Alice contributed long ago, Bob contributed recently
+        tracker.record_resolution(&params(), &node("Alice"), Satoshi(100), &Duration(1), true, &Timestamp(0));
+        tracker.record_resolution(&params(), &node("Bob"), Satoshi(50), &Duration(1), true, &Timestamp(500));
+        // only Bob's fees count towards the revenue to protect
+        assert_eq!(tracker.get_revenue(&params(), &Timestamp(500)), 50);
+        assert_eq!(tracker.get_reputation(&params(), &node("Alice"), &Timestamp(500)), 100);
+        assert!(tracker.has_good_reputation(&params(), &node("Alice"), &Timestamp(500)));
+        assert!(tracker.has_good_reputation(&params(), &node("Bob"), &Timestamp(500)));
+        assert!(!tracker.has_good_reputation(&params(), &node("Charlie"), &Timestamp(500)));
+        // Alice's contribution falls out of the reputation window
+        assert_eq!(tracker.get_reputation(&params(), &node("Alice"), &Timestamp(1000)), 0);
+    }
+
+    #[test]
+    fn reputation_prunes_old_htlcs() {
+        let mut tracker = ReputationTracker::new();
+        for time in [0, 500, 999] {
+            tracker.record_resolution(&params(), &node("Alice"), Satoshi(10), &Duration(1), false, &Timestamp(time));
+        }
+        assert_eq!(tracker.get_num_resolved_htlcs(), 3);
+        // the reputation window is 1000 long: the first HTLC no longer counts
+        tracker.record_resolution(&params(), &node("Alice"), Satoshi(10), &Duration(1), false, &Timestamp(1000));
+        assert_eq!(tracker.get_num_resolved_htlcs(), 3);
+        assert_eq!(tracker.get_reputation(&params(), &node("Alice"), &Timestamp(1000)), 30);
+    }
+
+    #[test]
+    fn reputation_slow_endorsed_htlcs_cost_reputation() {
+        let mut tracker = ReputationTracker::new();
+        tracker.record_resolution(&params(), &node("Alice"), Satoshi(1000), &Duration(1), true, &Timestamp(0));
+        // revenue is 1000 per 10 periods, i.e., 100 per period
+        // the jam is held for 5 periods, 4 of which are charged
+        tracker.record_resolution(&params(), &node("Bob"), Satoshi(0), &Duration(50), true, &Timestamp(1));
+        assert_eq!(tracker.get_reputation(&params(), &node("Bob"), &Timestamp(1)), -400);
+        // holding unendorsed HTLCs is free
+        tracker.record_resolution(&params(), &node("Charlie"), Satoshi(0), &Duration(50), false, &Timestamp(1));
+        assert_eq!(tracker.get_reputation(&params(), &node("Charlie"), &Timestamp(1)), 0);
+    }
+}
@@ -0,0 +1,265 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::channel::direction::Direction as Direction;
+use crate::common::{channelid::ChannelId, nodeid::NodeId, satoshi::Satoshi, timestamp::Timestamp};
+use crate::network::Network as Network;
+use crate::sender::scorer::Scorer as Scorer;
+
+//////////////////// ROUTER.PY //////////////////////////
+
+// Routes are lists of node IDs, from the sender to the receiver,
+// with the channel the router picked for each hop (nodes may share several channels).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    pub nodes: Vec<NodeId>,
+    pub cids: Vec<ChannelId>,
+}
+
+pub fn find_route(
+    network: &Network,
+    sender: &NodeId,
+    receiver: &NodeId,
+    must_route_via_nodes: &[NodeId],
+    excluded_channels: &[ChannelId],
+    excluded_nodes: &[NodeId],
+) -> Option<Route> {
+    // Find the shortest route (by the number of hops).
+    find_route_with_weights(
+        network,
+        sender,
+        receiver,
+        must_route_via_nodes,
+        excluded_channels,
+        excluded_nodes,
+        |_from, _to, _cid| Some(1.0),
+    )
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn find_route_scored(
+    network: &Network,
+    sender: &NodeId,
+    receiver: &NodeId,
+    must_route_via_nodes: &[NodeId],
+    excluded_channels: &[ChannelId],
+    excluded_nodes: &[NodeId],
+    scorer: &Scorer,
+    amount: &Satoshi,
+    time: &Timestamp,
+) -> Option<Route> {
+    // Find the route with the best trade-off between length and success probability.
+    // Each hop costs 1 plus the scorer's penalty,
+    // which is zero for channel directions that surely succeed.
+    // We don't account for fees accumulated along the route: all hops forward the same amount.
+    find_route_with_weights(
+        network,
+        sender,
+        receiver,
+        must_route_via_nodes,
+        excluded_channels,
+        excluded_nodes,
+        |from, to, cid| {
+            let capacity = network.get_channel(cid)?.get_capacity();
+            let penalty = scorer.get_penalty(cid, &Direction::new(from, to), capacity, amount, time)?;
+            Some(1.0 + penalty)
+        },
+    )
+}
+
+fn find_route_with_weights<F>(
+    network: &Network,
+    sender: &NodeId,
+    receiver: &NodeId,
+    must_route_via_nodes: &[NodeId],
+    excluded_channels: &[ChannelId],
+    excluded_nodes: &[NodeId],
+    hop_weight: F,
+) -> Option<Route>
+where
+    F: Fn(&NodeId, &NodeId, &ChannelId) -> Option<f64>,
+{
+    // Find the cheapest route that goes through must_route_via_nodes in the given order.
+    // We concatenate cheapest paths between consecutive waypoints,
+    // so the resulting route may contain loops.
+    let mut waypoints = vec![sender];
+    waypoints.extend(must_route_via_nodes);
+    waypoints.push(receiver);
+    let mut route = Route { nodes: vec![sender.clone()], cids: Vec::new() };
+    for segment in waypoints.windows(2) {
+        let path = find_cheapest_path(
+            network, segment[0], segment[1], excluded_channels, excluded_nodes, &hop_weight)?;
+        route.nodes.extend(path.nodes.into_iter().skip(1));
+        route.cids.extend(path.cids);
+    }
+    Some(route)
+}
+
+#[derive(Debug, PartialEq)]
+struct QueueEntry<'a> {
+    cost: f64,
+    node: &'a NodeId,
+}
+
+impl Eq for QueueEntry<'_> {}
+
+impl Ord for QueueEntry<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap is a max-heap: reverse the order to pop the cheapest entry first,
+        // breaking ties by node ID to make routes deterministic
+        other.cost.total_cmp(&self.cost).then_with(|| other.node.cmp(self.node))
+    }
+}
+
+impl PartialOrd for QueueEntry<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn find_cheapest_path<F>(
+    network: &Network,
+    from: &NodeId,
+    to: &NodeId,
+    excluded_channels: &[ChannelId],
+    excluded_nodes: &[NodeId],
+    hop_weight: &F,
+) -> Option<Route>
+where
+    F: Fn(&NodeId, &NodeId, &ChannelId) -> Option<f64>,
+{
+    // Dijkstra's algorithm; hops with no weight are unusable
+    let mut costs: HashMap<&NodeId, f64> = HashMap::from([(from, 0.0)]);
+    // for each node reached: the previous node and the channel from it
+    let mut predecessors: HashMap<&NodeId, (&NodeId, &ChannelId)> = HashMap::new();
+    let mut queue = BinaryHeap::from([QueueEntry { cost: 0.0, node: from }]);
+    while let Some(QueueEntry { cost, node }) = queue.pop() {
+        if node == to {
+            let mut path = Route { nodes: vec![to.clone()], cids: Vec::new() };
+            let mut current = to;
+            while current != from {
+                let (predecessor, cid) = predecessors[current];
+                current = predecessor;
+                path.nodes.push(current.clone());
+                path.cids.push(cid.clone());
+            }
+            path.nodes.reverse();
+            path.cids.reverse();
+            return Some(path);
+        }
+        if cost > costs[node] {
+            continue;
+        }
+        for neighbor in network.get_neighbors(node) {
+            // the endpoints are never excluded
+            if neighbor != to && excluded_nodes.contains(neighbor) {
+                continue;
+            }
+            // of the usable channels to the neighbor, take the cheapest (the first one on ties)
+            let cheapest = network
+                .get_cids_in_direction(node, neighbor)
+                .filter(|cid| !excluded_channels.contains(cid))
+                .filter_map(|cid| hop_weight(node, neighbor, cid).map(|weight| (weight, cid)))
+                .reduce(|cheapest, candidate| if candidate.0 < cheapest.0 { candidate } else { cheapest });
+            let Some((weight, cid)) = cheapest else {
+                continue;
+            };
+            let neighbor_cost = cost + weight;
+            if costs.get(neighbor).is_none_or(|&known_cost| neighbor_cost < known_cost) {
+                costs.insert(neighbor, neighbor_cost);
+                predecessors.insert(neighbor, (node, cid));
+                queue.push(QueueEntry { cost: neighbor_cost, node: neighbor });
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channel::channelindirection::ChannelInDirection;
+    use crate::common::duration::Duration;
+    use crate::sender::scorer::ScorerParams;
+    use crate::testutils::{self, cid, node, nodes};
+
+    fn add_channel(network: &mut Network, from: &str, to: &str) {
+        testutils::add_bidirectional_channel(network, from, to, || ChannelInDirection::new(None, None, None, None, None));
+    }
+
+    fn example_network() -> Network {
+        //   Alice - Bob - Dave
+        //     \           /
+        //      Charlie --
+        let mut network = Network::new(None);
+        add_channel(&mut network, "Alice", "Bob");
+        add_channel(&mut network, "Bob", "Dave");
+        add_channel(&mut network, "Alice", "Charlie");
+        add_channel(&mut network, "Charlie", "Dave");
+        network
+    }
+
+    #[test]
+    fn router_shortest_route() {
+        let network = example_network();
+        let route = find_route(&network, &node("Alice"), &node("Dave"), &[], &[], &[]).map(|route| route.nodes);
+        // Bob comes before Charlie alphabetically
+        assert_eq!(route, Some(nodes(&["Alice", "Bob", "Dave"])));
+    }
+
+    #[test]
+    fn router_exclusions() {
+        let network = example_network();
+        let route = find_route(
+            &network, &node("Alice"), &node("Dave"), &[], &[cid("Bob", "Dave")], &[]).map(|route| route.nodes);
+        assert_eq!(route, Some(nodes(&["Alice", "Charlie", "Dave"])));
+        let route = find_route(
+            &network, &node("Alice"), &node("Dave"), &[], &[], &[node("Charlie"), node("Bob")]).map(|route| route.nodes);
+        assert_eq!(route, None);
+    }
+
+    #[test]
+    fn router_parallel_channels() {
+        // Alice and Bob have a second channel, which the router only picks if the first one is excluded
+        let mut network = example_network();
+        add_channel(&mut network, "Bob", "Alice");
+        let route = find_route(&network, &node("Alice"), &node("Bob"), &[], &[], &[]).unwrap();
+        assert_eq!(route.cids, vec![cid("Alice", "Bob")]);
+        let route = find_route(&network, &node("Alice"), &node("Bob"), &[], &[cid("Alice", "Bob")], &[]).unwrap();
+        assert_eq!(route.nodes, nodes(&["Alice", "Bob"]));
+        assert_eq!(route.cids, vec![cid("Bob", "Alice")]);
+    }
+
+    #[test]
+    fn router_scored_route_avoids_failed_channel() {
+        let network = example_network();
+        let mut scorer = Scorer::new(ScorerParams {
+            half_life: Duration(10),
+            penalty_multiplier: 1.0,
+        });
+        let amount = Satoshi(1000);
+        let route = |scorer: &Scorer, time| find_route_scored(
+            &network, &node("Alice"), &node("Dave"), &[], &[], &[], scorer, &amount, &Timestamp(time)).map(|route| route.nodes);
+        assert_eq!(route(&scorer, 0), Some(nodes(&["Alice", "Bob", "Dave"])));
+        // a failure makes the sender avoid the channel for a while
+        scorer.record_failure(
+            &cid("Bob", "Dave"), &Direction::new(&node("Bob"), &node("Dave")),
+            &Satoshi(1_000_000), &Satoshi(1), &Timestamp(0));
+        assert_eq!(route(&scorer, 0), Some(nodes(&["Alice", "Charlie", "Dave"])));
+        assert_eq!(route(&scorer, 1), Some(nodes(&["Alice", "Charlie", "Dave"])));
+        // after many half-lives, the channel's reputation has fully recovered
+        assert_eq!(route(&scorer, 1000), Some(nodes(&["Alice", "Bob", "Dave"])));
+    }
+
+    #[test]
+    fn router_must_route_via_nodes() {
+        let network = example_network();
+        let route = find_route(
+            &network, &node("Alice"), &node("Dave"), &[node("Charlie")], &[], &[]).map(|route| route.nodes);
+        assert_eq!(route, Some(nodes(&["Alice", "Charlie", "Dave"])));
+        // a circular route from Alice back to Alice via Bob and Dave
+        let route = find_route(
+            &network, &node("Alice"), &node("Alice"), &[node("Bob"), node("Dave")], &[], &[]).map(|route| route.nodes);
+        assert_eq!(route, Some(nodes(&["Alice", "Bob", "Dave", "Bob", "Alice"])));
+    }
+}
@@ -1,4 +1,8 @@
+use rand::Rng;
+
 use crate::common::{nodeid::NodeId, satoshi::Satoshi, duration::Duration, paymentresult::PaymentResult};
+use crate::common::trafficclass::{HoldTimeDistribution, TrafficClass};
+use crate::sender::RetryState;
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Event {
@@ -9,6 +13,9 @@ pub struct Event {
     pub processing_delay: Duration,
     pub desired_result: PaymentResult,
     pub must_route_via_nodes: Option<Vec<NodeId>>,
+    pub traffic_class: TrafficClass,
+    // None for the first attempt
+    pub retry_state: Option<RetryState>,
 }
 
 impl Event {
@@ -31,6 +38,22 @@ impl Event {
             processing_delay,
             desired_result,
             must_route_via_nodes,
+            traffic_class: TrafficClass::Regular,
+            retry_state: None,
+        }
+    }
+
+    pub fn new_hold_invoice<R: Rng>(
+        sender: NodeId,
+        receiver: NodeId,
+        amount: Satoshi,
+        hold_time: &HoldTimeDistribution,
+        rng: &mut R,
+    ) -> Self {
+        // the receiver holds the HTLCs (e.g., until a swap's on-chain leg confirms), then settles
+        Self {
+            traffic_class: TrafficClass::HoldInvoice,
+            ..Event::new(sender, receiver, amount, hold_time.sample(rng), PaymentResult::SUCCESS, None)
         }
     }
 }
@@ -38,6 +61,7 @@ impl Event {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
 
     #[test]
     #[should_panic]
@@ -51,4 +75,19 @@ mod tests {
             None,
         );
     }
+
+    #[test]
+    pub fn event_hold_invoice() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let event = Event::new_hold_invoice(
+            NodeId(String::from("Alice")),
+            NodeId(String::from("Bob")),
+            Satoshi(200),
+            &HoldTimeDistribution::new(Duration(60), Duration(3600)),
+            &mut rng,
+        );
+        assert_eq!(event.traffic_class, TrafficClass::HoldInvoice);
+        assert!(event.processing_delay >= Duration(60));
+        assert_eq!(event.desired_result, PaymentResult::SUCCESS);
+    }
 }
\ No newline at end of file
@@ -27,6 +27,9 @@ impl Schedule {
             schedule: PriorityQueue::new(),
         }
     }
+    pub fn get_end_time(&self) -> &Timestamp {
+        &self.end_time
+    }
     pub fn get_num_events(&self) -> usize {
         self.schedule.len()
     }
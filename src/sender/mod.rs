@@ -0,0 +1,164 @@
+pub mod mpp;
+pub mod scorer;
+
+use crate::common::{
+    channelid::ChannelId,
+    duration::Duration,
+    errortype::ErrorType,
+    nodeid::NodeId,
+    paymentfailure::PaymentFailure,
+    timestamp::Timestamp,
+};
+
+use mpp::SplitStrategy;
+use scorer::ScorerParams;
+
+#[derive(Debug, Clone)]
+pub struct SenderParams {
+    pub max_num_attempts: u32,
+    // the time budget is counted from the first attempt
+    pub max_retry_time: Duration,
+    // how long it takes the sender to learn about a failure and try again
+    pub retry_delay: Duration,
+    // whether to drop the must_route_via_nodes constraint if no route satisfies it
+    pub reroute_around_must_route_via: bool,
+    // if set, senders learn from past payments and weight routes by success probability
+    pub scorer_params: Option<ScorerParams>,
+    pub split_strategy: SplitStrategy,
+    // how long the receiver waits for the rest of a multi-part payment once a part has arrived
+    pub mpp_timeout: Duration,
+}
+
+impl Default for SenderParams {
+    fn default() -> Self {
+        // by default, senders don't retry (as in the Python implementation)
+        SenderParams {
+            max_num_attempts: 1,
+            max_retry_time: Duration(0),
+            retry_delay: Duration(1),
+            reroute_around_must_route_via: false,
+            scorer_params: None,
+            split_strategy: SplitStrategy::NoSplit,
+            mpp_timeout: Duration(60),
+        }
+    }
+}
+
+// What the sender remembers between attempts to send the same payment.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RetryState {
+    pub num_attempts: u32,
+    pub first_attempt_time: Timestamp,
+    pub excluded_channels: Vec<ChannelId>,
+    pub excluded_nodes: Vec<NodeId>,
+}
+
+impl RetryState {
+    pub fn new(first_attempt_time: Timestamp) -> Self {
+        RetryState {
+            num_attempts: 0,
+            first_attempt_time,
+            excluded_channels: Vec::new(),
+            excluded_nodes: Vec::new(),
+        }
+    }
+
+    pub fn learn_from_failure(&mut self, route: &[NodeId], failure: &PaymentFailure) -> bool {
+        // Update exclusions the way real pathfinding does, based on the BOLT 4 failure code.
+        // Return whether another attempt can possibly succeed.
+        // The erring node is the one that failed to forward along the erring hop.
+        let erring_node = &route[failure.erring_hop];
+        let is_sender = failure.erring_hop == 0;
+        match failure.error_type.to_bolt4() {
+            // the receiver rejected the payment: retrying won't help
+            ErrorType::IncorrectOrUnknownPaymentDetails => false,
+            // the channel update carries the new fees: retry with them along the same route
+            ErrorType::FeeInsufficient => true,
+            error_type if error_type.is_node_failure() && !is_sender => {
+                if !self.excluded_nodes.contains(erring_node) {
+                    self.excluded_nodes.push(erring_node.clone());
+                }
+                true
+            }
+            _ => {
+                if !self.excluded_channels.contains(&failure.erring_channel) {
+                    self.excluded_channels.push(failure.erring_channel.clone());
+                }
+                true
+            }
+        }
+    }
+
+    pub fn can_retry(&self, sender_params: &SenderParams, retry_time: &Timestamp) -> bool {
+        self.num_attempts < sender_params.max_num_attempts
+            && retry_time <= &self.first_attempt_time.add(&sender_params.max_retry_time)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutils::node;
+
+    fn route() -> Vec<NodeId> {
+        vec![node("Alice"), node("Bob"), node("Charlie")]
+    }
+
+    fn failure(error_type: ErrorType, erring_hop: usize) -> PaymentFailure {
+        PaymentFailure {
+            error_type,
+            erring_hop,
+            erring_channel: ChannelId(String::from("BobCharlie")),
+        }
+    }
+
+    #[test]
+    fn retrystate_exclude_channel() {
+        let mut retry_state = RetryState::new(Timestamp(0));
+        assert!(retry_state.learn_from_failure(&route(), &failure(ErrorType::NoSlots, 1)));
+        assert!(retry_state.learn_from_failure(&route(), &failure(ErrorType::TemporaryChannelFailure, 1)));
+        assert_eq!(retry_state.excluded_channels, vec![ChannelId(String::from("BobCharlie"))]);
+        assert!(retry_state.excluded_nodes.is_empty());
+    }
+
+    #[test]
+    fn retrystate_exclude_node() {
+        let mut retry_state = RetryState::new(Timestamp(0));
+        assert!(retry_state.learn_from_failure(&route(), &failure(ErrorType::TemporaryNodeFailure, 1)));
+        assert_eq!(retry_state.excluded_nodes, vec![node("Bob")]);
+        assert!(retry_state.excluded_channels.is_empty());
+        // the sender doesn't exclude itself
+        let mut retry_state = RetryState::new(Timestamp(0));
+        assert!(retry_state.learn_from_failure(&route(), &failure(ErrorType::PermanentNodeFailure, 0)));
+        assert!(retry_state.excluded_nodes.is_empty());
+    }
+
+    #[test]
+    fn retrystate_no_exclusion() {
+        let mut retry_state = RetryState::new(Timestamp(0));
+        assert!(retry_state.learn_from_failure(&route(), &failure(ErrorType::LowFee, 1)));
+        assert!(!retry_state.learn_from_failure(
+            &route(), &failure(ErrorType::IncorrectOrUnknownPaymentDetails, 1)));
+        assert!(retry_state.excluded_channels.is_empty());
+        assert!(retry_state.excluded_nodes.is_empty());
+    }
+
+    #[test]
+    fn retrystate_can_retry() {
+        let sender_params = SenderParams {
+            max_num_attempts: 2,
+            max_retry_time: Duration(10),
+            retry_delay: Duration(1),
+            reroute_around_must_route_via: false,
+            ..SenderParams::default()
+        };
+        let mut retry_state = RetryState::new(Timestamp(5));
+        retry_state.num_attempts = 1;
+        assert!(retry_state.can_retry(&sender_params, &Timestamp(15)));
+        assert!(!retry_state.can_retry(&sender_params, &Timestamp(16)));
+        // by default, there are no retries
+        assert!(!retry_state.can_retry(&SenderParams::default(), &Timestamp(6)));
+        retry_state.num_attempts = 2;
+        assert!(!retry_state.can_retry(&sender_params, &Timestamp(6)));
+    }
+}
@@ -0,0 +1,63 @@
+use crate::common::{params, satoshi::Satoshi};
+
+// How senders split payments into parts (multi-part payments, MPP).
+// The receiver only settles the payment once all parts have arrived.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SplitStrategy {
+    NoSplit,
+    // split into the given number of equal parts
+    EqualParts(usize),
+    // split into as few equal parts as possible, none of them larger than the given amount
+    MaxPartAmount(Satoshi),
+}
+
+impl SplitStrategy {
+    pub fn split(&self, amount: &Satoshi) -> Vec<Satoshi> {
+        let num_parts = match self {
+            SplitStrategy::NoSplit => 1,
+            SplitStrategy::EqualParts(num_parts) => *num_parts,
+            SplitStrategy::MaxPartAmount(max_part_amount) => {
+                assert!(max_part_amount.0 > 0, "Maximal part amount must be positive");
+                amount.0.div_ceil(max_part_amount.0) as usize
+            }
+        };
+        // parts must not be dust
+        let max_num_parts = std::cmp::max(1, (amount.0 / params::DUST_LIMIT.0) as usize);
+        let num_parts = num_parts.clamp(1, max_num_parts) as u64;
+        // the remainder is spread over the first parts
+        (0..num_parts)
+            .map(|i| Satoshi(amount.0 / num_parts + if i < amount.0 % num_parts { 1 } else { 0 }))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_strategy_split() {
+        let amount = Satoshi(10_000);
+        assert_eq!(SplitStrategy::NoSplit.split(&amount), vec![amount]);
+        assert_eq!(
+            SplitStrategy::EqualParts(3).split(&amount),
+            vec![Satoshi(3334), Satoshi(3333), Satoshi(3333)],
+        );
+        assert_eq!(
+            SplitStrategy::MaxPartAmount(Satoshi(4000)).split(&amount),
+            vec![Satoshi(3334), Satoshi(3333), Satoshi(3333)],
+        );
+        assert_eq!(SplitStrategy::MaxPartAmount(Satoshi(20_000)).split(&amount), vec![amount]);
+    }
+
+    #[test]
+    fn split_strategy_no_dust_parts() {
+        // 1000 satoshi only fit two parts above the dust limit
+        assert_eq!(
+            SplitStrategy::EqualParts(5).split(&Satoshi(1000)),
+            vec![Satoshi(500), Satoshi(500)],
+        );
+        // amounts below the dust limit are not split
+        assert_eq!(SplitStrategy::EqualParts(5).split(&Satoshi(100)), vec![Satoshi(100)]);
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+
+use crate::channel::direction::Direction as Direction;
+use crate::common::{
+    channelid::ChannelId,
+    duration::Duration,
+    satoshi::Satoshi,
+    timestamp::Timestamp,
+};
+
+// A sender-side estimate of how likely each channel direction is to forward a payment,
+// similar to LDK's ProbabilisticScorer and lnd's mission control.
+// The sender doesn't know the liquidity of remote channels,
+// so it keeps bounds on it, learned from the outcomes of its own payments.
+// Without observations, liquidity is uniformly distributed in [0, capacity].
+// Note: a jammed channel fails any amount, so it ends up with an upper bound of zero,
+// and the sender routes around it until the bound decays.
+
+#[derive(Debug, Clone)]
+pub struct ScorerParams {
+    // the time it takes learned bounds to move halfway back to [0, capacity]
+    pub half_life: Duration,
+    // how strongly to prefer likely routes over short ones
+    pub penalty_multiplier: f64,
+}
+
+impl Default for ScorerParams {
+    fn default() -> Self {
+        ScorerParams {
+            half_life: Duration(60),
+            penalty_multiplier: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct LiquidityBounds {
+    min_liquidity: Satoshi,
+    max_liquidity: Satoshi,
+    last_updated: Timestamp,
+}
+
+#[derive(Debug, Clone)]
+pub struct Scorer {
+    params: ScorerParams,
+    bounds: HashMap<(ChannelId, Direction), LiquidityBounds>,
+}
+
+impl Scorer {
+    pub fn new(params: ScorerParams) -> Self {
+        Scorer {
+            params,
+            bounds: HashMap::new(),
+        }
+    }
+
+    fn get_decayed_bounds(
+        &self,
+        cid: &ChannelId,
+        direction: &Direction,
+        capacity: &Satoshi,
+        time: &Timestamp,
+    ) -> (Satoshi, Satoshi) {
+        match self.bounds.get(&(cid.clone(), *direction)) {
+            None => (Satoshi(0), *capacity),
+            Some(bounds) => {
+                // offsets from the default bounds shrink by half every half-life
+                let elapsed = time.0.saturating_sub(bounds.last_updated.0) as f64;
+                let decay = if self.params.half_life.0 == 0 {
+                    0.0
+                } else {
+                    0.5_f64.powf(elapsed / self.params.half_life.0 as f64)
+                };
+                let min_liquidity = bounds.min_liquidity.mul(decay);
+                let max_liquidity = capacity.sub(&capacity.sub(&bounds.max_liquidity).mul(decay));
+                (min_liquidity, max_liquidity)
+            }
+        }
+    }
+
+    pub fn success_probability(
+        &self,
+        cid: &ChannelId,
+        direction: &Direction,
+        capacity: &Satoshi,
+        amount: &Satoshi,
+        time: &Timestamp,
+    ) -> f64 {
+        let (min_liquidity, max_liquidity) = self.get_decayed_bounds(cid, direction, capacity, time);
+        if amount.le(&min_liquidity) {
+            1.0
+        } else if amount.gt(&max_liquidity) {
+            0.0
+        } else {
+            // uniform liquidity distribution between the bounds
+            (max_liquidity.0 - amount.0) as f64 / (max_liquidity.0 - min_liquidity.0) as f64
+        }
+    }
+
+    pub fn get_penalty(
+        &self,
+        cid: &ChannelId,
+        direction: &Direction,
+        capacity: &Satoshi,
+        amount: &Satoshi,
+        time: &Timestamp,
+    ) -> Option<f64> {
+        // The penalty is the negative log of the success probability,
+        // so that minimizing the sum of penalties maximizes the route's success probability.
+        // Channel directions that surely fail are not used at all.
+        let success_probability = self.success_probability(cid, direction, capacity, amount, time);
+        if success_probability > 0.0 {
+            Some(-success_probability.ln() * self.params.penalty_multiplier)
+        } else {
+            None
+        }
+    }
+
+    pub fn record_success(
+        &mut self,
+        cid: &ChannelId,
+        direction: &Direction,
+        capacity: &Satoshi,
+        amount: &Satoshi,
+        time: &Timestamp,
+    ) {
+        // the channel direction could forward the amount
+        let (min_liquidity, max_liquidity) = self.get_decayed_bounds(cid, direction, capacity, time);
+        let min_liquidity = *std::cmp::min(std::cmp::max(&min_liquidity, amount), capacity);
+        let max_liquidity = *std::cmp::max(&max_liquidity, &min_liquidity);
+        self.set_bounds(cid, direction, min_liquidity, max_liquidity, time);
+    }
+
+    pub fn record_failure(
+        &mut self,
+        cid: &ChannelId,
+        direction: &Direction,
+        capacity: &Satoshi,
+        amount: &Satoshi,
+        time: &Timestamp,
+    ) {
+        // the channel direction couldn't forward the amount
+        let (min_liquidity, max_liquidity) = self.get_decayed_bounds(cid, direction, capacity, time);
+        let max_liquidity = *std::cmp::min(&max_liquidity, &Satoshi(amount.0.saturating_sub(1)));
+        let min_liquidity = *std::cmp::min(&min_liquidity, &max_liquidity);
+        self.set_bounds(cid, direction, min_liquidity, max_liquidity, time);
+    }
+
+    fn set_bounds(
+        &mut self,
+        cid: &ChannelId,
+        direction: &Direction,
+        min_liquidity: Satoshi,
+        max_liquidity: Satoshi,
+        time: &Timestamp,
+    ) {
+        self.bounds.insert(
+            (cid.clone(), *direction),
+            LiquidityBounds {
+                min_liquidity,
+                max_liquidity,
+                last_updated: time.clone(),
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cid() -> ChannelId {
+        ChannelId(String::from("cid0"))
+    }
+
+    fn scorer() -> Scorer {
+        Scorer::new(ScorerParams {
+            half_life: Duration(10),
+            penalty_multiplier: 1.0,
+        })
+    }
+
+    #[test]
+    fn scorer_prior_from_capacity() {
+        let scorer = scorer();
+        let capacity = Satoshi(1000);
+        let p = |amount| scorer.success_probability(&cid(), &Direction::Alph, &capacity, &Satoshi(amount), &Timestamp(0));
+        assert_eq!(p(0), 1.0);
+        assert_eq!(p(250), 0.75);
+        assert_eq!(p(1000), 0.0);
+        assert_eq!(p(1001), 0.0);
+    }
+
+    #[test]
+    fn scorer_learns_from_outcomes() {
+        let mut scorer = scorer();
+        let capacity = Satoshi(1000);
+        scorer.record_success(&cid(), &Direction::Alph, &capacity, &Satoshi(200), &Timestamp(0));
+        scorer.record_failure(&cid(), &Direction::Alph, &capacity, &Satoshi(601), &Timestamp(0));
+        let p = |amount| scorer.success_probability(&cid(), &Direction::Alph, &capacity, &Satoshi(amount), &Timestamp(0));
+        assert_eq!(p(200), 1.0);
+        assert_eq!(p(400), 0.5);
+        assert_eq!(p(601), 0.0);
+        // the other direction is unaffected
+        let p_other = scorer.success_probability(&cid(), &Direction::NonAlph, &capacity, &Satoshi(400), &Timestamp(0));
+        assert_eq!(p_other, 0.6);
+    }
+
+    #[test]
+    fn scorer_decay() {
+        let mut scorer = scorer();
+        let capacity = Satoshi(1000);
+        // a jammed channel fails even the smallest payments
+        scorer.record_failure(&cid(), &Direction::Alph, &capacity, &Satoshi(1), &Timestamp(0));
+        let p = |scorer: &Scorer, time| scorer.success_probability(&cid(), &Direction::Alph, &capacity, &Satoshi(100), &Timestamp(time));
+        assert_eq!(p(&scorer, 0), 0.0);
+        assert!(scorer.get_penalty(&cid(), &Direction::Alph, &capacity, &Satoshi(100), &Timestamp(0)).is_none());
+        // after one half-life, the upper bound is back at 500
+        assert_eq!(p(&scorer, 10), 0.8);
+        // after a long time, the prior is restored
+        assert!((p(&scorer, 1000) - 0.9).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,750 @@
+use std::collections::HashMap;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+use crate::common::{
+    nodeid::NodeId,
+    paymentfailure::PaymentFailure,
+    paymentid::PaymentId,
+    satoshi::Satoshi,
+    timestamp::Timestamp,
+    trafficclass::TrafficClass,
+};
+use crate::attacker::Attacker as Attacker;
+use crate::channel::direction::Direction as Direction;
+use crate::network::Network as Network;
+use crate::router::{self, Route};
+use crate::schedule::event::Event as Event;
+use crate::schedule::schedule::Schedule as Schedule;
+use crate::sender::{RetryState, SenderParams};
+use crate::sender::scorer::Scorer as Scorer;
+
+//////////////////// SIMULATOR.PY //////////////////////////
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SimulationStats {
+    pub num_events: u64,
+    pub num_attempts: u64,
+    // each attempt sends one part, or more for multi-part payments
+    pub num_parts_sent: u64,
+    // the payment has reached the receiver (which may still fail it deliberately)
+    pub num_reached_receiver: u64,
+    // all attempts failed, or no route was found
+    pub num_failed: u64,
+}
+
+#[derive(Debug)]
+pub struct Simulator {
+    network: Network,
+    schedule: Schedule,
+    sender_params: SenderParams,
+    // each sender learns from its own payments only
+    scorers: HashMap<NodeId, Scorer>,
+    rng: StdRng,
+    stats: SimulationStats,
+    stats_by_class: HashMap<TrafficClass, SimulationStats>,
+    // the honest payment parts that have made it through each channel direction to the receiver
+    num_parts_through: HashMap<(NodeId, NodeId), u64>,
+    attackers: Vec<Box<dyn Attacker>>,
+}
+
+impl Simulator {
+    pub fn new(
+        network: Network,
+        schedule: Schedule,
+        sender_params: Option<SenderParams>,
+        seed: u64,
+    ) -> Self {
+        let sender_params = sender_params.unwrap_or_default();
+        // retries must be scheduled after the attempt that failed
+        assert!(sender_params.retry_delay.0 > 0, "Retry delay must be positive");
+        Simulator {
+            network,
+            schedule,
+            sender_params,
+            scorers: HashMap::new(),
+            rng: StdRng::seed_from_u64(seed),
+            stats: SimulationStats::default(),
+            stats_by_class: HashMap::new(),
+            num_parts_through: HashMap::new(),
+            attackers: Vec::new(),
+        }
+    }
+
+    pub fn get_network(&self) -> &Network {
+        &self.network
+    }
+
+    pub fn get_end_time(&self) -> &Timestamp {
+        self.schedule.get_end_time()
+    }
+
+    pub fn get_stats(&self) -> &SimulationStats {
+        &self.stats
+    }
+
+    pub fn get_class_stats(&self, traffic_class: &TrafficClass) -> SimulationStats {
+        self.stats_by_class.get(traffic_class).cloned().unwrap_or_default()
+    }
+
+    fn count(&mut self, traffic_class: TrafficClass, update: fn(&mut SimulationStats)) {
+        // the totals and the stats of the event's traffic class
+        update(&mut self.stats);
+        update(self.stats_by_class.entry(traffic_class).or_default());
+    }
+
+    pub fn get_num_parts_through(&self, from: &NodeId, to: &NodeId) -> u64 {
+        self.num_parts_through.get(&(from.clone(), to.clone())).copied().unwrap_or(0)
+    }
+
+    pub fn add_attacker(&mut self, attacker: Box<dyn Attacker>) {
+        self.attackers.push(attacker);
+    }
+
+    pub fn get_attackers(&self) -> &[Box<dyn Attacker>] {
+        &self.attackers
+    }
+
+    pub fn run(&mut self) -> &SimulationStats {
+        while let (Some(time), Some(event)) = self.schedule.get_event() {
+            // attackers act right before honest payments, so they always see the latest state
+            for attacker in self.attackers.iter_mut() {
+                attacker.act(&mut self.network, &time, &mut self.rng);
+            }
+            self.handle_event(time, event);
+        }
+        self.network.resolve_all_htlcs();
+        &self.stats
+    }
+
+    fn handle_event(&mut self, time: Timestamp, event: Event) {
+        let traffic_class = event.traffic_class;
+        let mut retry_state = match &event.retry_state {
+            Some(retry_state) => retry_state.clone(),
+            None => {
+                self.count(traffic_class, |stats| stats.num_events += 1);
+                RetryState::new(time.clone())
+            }
+        };
+        // the sender prices the payment by what has been gossiped so far
+        self.network.propagate_gossip(&time);
+        let parts = self.sender_params.split_strategy.split(&event.amount);
+        let Some(routes) = self.find_part_routes(&event, &parts, &retry_state, &time) else {
+            self.count(traffic_class, |stats| stats.num_failed += 1);
+            return;
+        };
+        retry_state.num_attempts += 1;
+        self.count(traffic_class, |stats| stats.num_attempts += 1);
+        // parts that have been forwarded to the receiver: their routes, payment IDs and arrival times
+        let mut sent_parts: Vec<(Route, PaymentId, Timestamp)> = Vec::new();
+        for (part, route) in parts.iter().zip(routes) {
+            let mut payment = self.network.create_payment_over(
+                &route.nodes,
+                &route.cids,
+                *part,
+                event.processing_delay.clone(),
+                event.desired_result.clone(),
+            );
+            payment.set_traffic_class(traffic_class);
+            self.count(traffic_class, |stats| stats.num_parts_sent += 1);
+            let result = self.network.attempt_send_payment_over(
+                &payment, &route.nodes, &route.cids, &time, &mut self.rng);
+            self.update_scorer(&event.sender, &route, part, &time, result.as_ref().err());
+            match result {
+                Ok((payment_id, arrival_time)) => {
+                    for nodes in route.nodes.windows(2) {
+                        *self.num_parts_through.entry((nodes[0].clone(), nodes[1].clone())).or_insert(0) += 1;
+                    }
+                    sent_parts.push((route, payment_id, arrival_time));
+                }
+                Err(failure) => {
+                    // The receiver never gets the full amount: the parts that have arrived are failed back right away.
+                    // Parts still on their way (with hop delays) wait at the receiver for the MPP timeout.
+                    for (sent_route, payment_id, arrival_time) in &sent_parts {
+                        let failure_time = if arrival_time <= &time {
+                            time.clone()
+                        } else {
+                            arrival_time.add(&self.sender_params.mpp_timeout)
+                        };
+                        self.network.fail_payment_at(payment_id, &sent_route.nodes, &failure_time);
+                    }
+                    self.retry_or_fail(time, event, retry_state, &route.nodes, &failure);
+                    return;
+                }
+            }
+        }
+        // The receiver resolves all parts together once the last one has arrived,
+        // unless it arrives after the MPP timeout: then the receiver fails all of them,
+        // those still on their way as they arrive.
+        let first_arrival_time = sent_parts.iter().map(|(_, _, arrival_time)| arrival_time).min().unwrap().clone();
+        let last_arrival_time = sent_parts.iter().map(|(_, _, arrival_time)| arrival_time).max().unwrap().clone();
+        let timeout = first_arrival_time.add(&self.sender_params.mpp_timeout);
+        if last_arrival_time > timeout {
+            for (sent_route, payment_id, arrival_time) in &sent_parts {
+                let failure_time = std::cmp::max(arrival_time, &timeout);
+                self.network.fail_payment_at(payment_id, &sent_route.nodes, failure_time);
+            }
+            self.count(traffic_class, |stats| stats.num_failed += 1);
+            return;
+        }
+        let resolution_time = last_arrival_time.add(&event.processing_delay);
+        for (sent_route, payment_id, _) in &sent_parts {
+            self.network.resolve_payment_no_earlier_than(payment_id, &sent_route.nodes, &resolution_time);
+        }
+        self.count(traffic_class, |stats| stats.num_reached_receiver += 1);
+    }
+
+    fn retry_or_fail(
+        &mut self,
+        time: Timestamp,
+        event: Event,
+        mut retry_state: RetryState,
+        route: &[NodeId],
+        failure: &PaymentFailure,
+    ) {
+        let retry_time = time.add(&self.sender_params.retry_delay);
+        if retry_state.learn_from_failure(route, failure)
+            && retry_state.can_retry(&self.sender_params, &retry_time)
+            && &retry_time <= self.schedule.get_end_time()
+        {
+            let retry_event = Event {
+                retry_state: Some(retry_state),
+                ..event
+            };
+            self.schedule.put_event(retry_time, retry_event, Some(time));
+        } else {
+            self.count(event.traffic_class, |stats| stats.num_failed += 1);
+        }
+    }
+
+    fn find_part_routes(
+        &self,
+        event: &Event,
+        parts: &[Satoshi],
+        retry_state: &RetryState,
+        time: &Timestamp,
+    ) -> Option<Vec<Route>> {
+        // each part avoids the channels used by the previous parts, if possible
+        let mut diverse_retry_state = retry_state.clone();
+        let mut routes = Vec::new();
+        for part in parts {
+            let route = self.find_route(event, part, &diverse_retry_state, time)
+                .or_else(|| self.find_route(event, part, retry_state, time))?;
+            diverse_retry_state.excluded_channels.extend(route.cids.iter().cloned());
+            routes.push(route);
+        }
+        Some(routes)
+    }
+
+    fn find_route(
+        &self,
+        event: &Event,
+        amount: &Satoshi,
+        retry_state: &RetryState,
+        time: &Timestamp,
+    ) -> Option<Route> {
+        let must_route_via_nodes = event.must_route_via_nodes.as_deref().unwrap_or(&[]);
+        let route = self.find_route_via(event, amount, must_route_via_nodes, retry_state, time);
+        if route.is_some() || must_route_via_nodes.is_empty() || !self.sender_params.reroute_around_must_route_via {
+            return route;
+        }
+        // all routes via the required nodes are excluded: route around them
+        self.find_route_via(event, amount, &[], retry_state, time)
+    }
+
+    fn find_route_via(
+        &self,
+        event: &Event,
+        amount: &Satoshi,
+        must_route_via_nodes: &[NodeId],
+        retry_state: &RetryState,
+        time: &Timestamp,
+    ) -> Option<Route> {
+        let Some(scorer_params) = &self.sender_params.scorer_params else {
+            return router::find_route(
+                &self.network,
+                &event.sender,
+                &event.receiver,
+                must_route_via_nodes,
+                &retry_state.excluded_channels,
+                &retry_state.excluded_nodes,
+            );
+        };
+        // a sender that hasn't sent anything yet only knows channel capacities
+        let new_scorer;
+        let scorer = match self.scorers.get(&event.sender) {
+            Some(scorer) => scorer,
+            None => {
+                new_scorer = Scorer::new(scorer_params.clone());
+                &new_scorer
+            }
+        };
+        router::find_route_scored(
+            &self.network,
+            &event.sender,
+            &event.receiver,
+            must_route_via_nodes,
+            &retry_state.excluded_channels,
+            &retry_state.excluded_nodes,
+            scorer,
+            amount,
+            time,
+        )
+    }
+
+    fn update_scorer(
+        &mut self,
+        sender: &NodeId,
+        route: &Route,
+        amount: &Satoshi,
+        time: &Timestamp,
+        failure: Option<&PaymentFailure>,
+    ) {
+        // Hops before the erring one have forwarded the payment, the erring one has not.
+        // Only liquidity failures tell about the erring hop's liquidity:
+        // fee, expiry and node failures, or a disabled channel, leave its bounds as they are.
+        let Some(scorer_params) = &self.sender_params.scorer_params else {
+            return;
+        };
+        let scorer = self.scorers
+            .entry(sender.clone())
+            .or_insert_with(|| Scorer::new(scorer_params.clone()));
+        for (hop, (nodes, cid)) in route.nodes.windows(2).zip(&route.cids).enumerate() {
+            let direction = Direction::new(&nodes[0], &nodes[1]);
+            match failure {
+                Some(failure) if failure.erring_hop == hop => {
+                    if failure.error_type.is_liquidity_failure() {
+                        let cid = &failure.erring_channel;
+                        let capacity = self.network.get_channel(cid).unwrap().get_capacity();
+                        scorer.record_failure(cid, &direction, capacity, amount, time);
+                    }
+                    break;
+                }
+                _ => {
+                    let capacity = self.network.get_channel(cid).unwrap().get_capacity();
+                    scorer.record_success(cid, &direction, capacity, amount, time);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attacker::JamTarget;
+    use crate::attacker::adaptive::{AdaptiveJammer, AdaptiveJammerParams, JamMode};
+    use crate::channel::channelindirection::ChannelInDirection;
+    use crate::common::{
+        duration::Duration,
+        errortype::ErrorType,
+        paymentresult::PaymentResult,
+    };
+    use crate::common::hopdelay::HopDelay;
+    use crate::common::holdfee::HoldFee;
+    use crate::common::fee::{Fee, FeeRate};
+    use crate::common::feepolicy::{CongestionFee, PricingCurve};
+    use crate::common::trafficclass::HoldTimeDistribution;
+    use crate::sender::mpp::SplitStrategy;
+    use crate::sender::scorer::ScorerParams;
+    use crate::testutils::{self, cid, node};
+
+    fn add_channel(network: &mut Network, from: &str, to: &str, deliberately_fail_prob: f64) {
+        let ch_in_dir = ChannelInDirection::new(None, None, None, Some(deliberately_fail_prob), None);
+        testutils::add_channel(network, from, to, ch_in_dir);
+    }
+
+    fn example_network() -> Network {
+        // Alice - Bob - Dave is the shortest route, but Bob always fails
+        // Alice - Charlie - Eve - Dave is the longer honest alternative
+        let mut network = Network::new(None);
+        add_channel(&mut network, "Alice", "Bob", 0.0);
+        add_channel(&mut network, "Bob", "Dave", 1.0);
+        add_channel(&mut network, "Alice", "Charlie", 0.0);
+        add_channel(&mut network, "Charlie", "Eve", 0.0);
+        add_channel(&mut network, "Eve", "Dave", 0.0);
+        network
+    }
+
+    fn example_schedule(must_route_via_nodes: Option<Vec<NodeId>>) -> Schedule {
+        let mut schedule = Schedule::new(Timestamp(10));
+        let event = Event::new(
+            node("Alice"),
+            node("Dave"),
+            Satoshi(100),
+            Duration(5),
+            PaymentResult::SUCCESS,
+            must_route_via_nodes,
+        );
+        schedule.put_event(Timestamp(0), event, None);
+        schedule
+    }
+
+    fn example_event(amount: Satoshi) -> Event {
+        Event::new(node("Alice"), node("Dave"), amount, Duration(5), PaymentResult::SUCCESS, None)
+    }
+
+    fn sender_params(max_num_attempts: u32, reroute_around_must_route_via: bool) -> SenderParams {
+        SenderParams {
+            max_num_attempts,
+            max_retry_time: Duration(5),
+            retry_delay: Duration(1),
+            reroute_around_must_route_via,
+            ..SenderParams::default()
+        }
+    }
+
+    #[test]
+    fn simulator_attacker_jams_honest_payment() {
+        // Mallory keeps Bob - Dave full, so Alice's payment fails
+        let mut network = Network::new(None);
+        add_channel(&mut network, "Alice", "Bob", 0.0);
+        add_channel(&mut network, "Mallory", "Bob", 0.0);
+        testutils::add_channel(&mut network, "Bob", "Dave", ChannelInDirection::new(Some(2), None, None, None, None));
+        let mut simulator = Simulator::new(network, example_schedule(None), None, 0);
+        let target = JamTarget::new(vec![node("Mallory"), node("Bob"), node("Dave")], 1);
+        simulator.add_attacker(Box::new(AdaptiveJammer::new(vec![target], AdaptiveJammerParams {
+            probe_amount: Satoshi(1),
+            jam_mode: JamMode::Slot(Satoshi(100)),
+            jam_hold_time: Duration(100),
+            budget: Satoshi(1000),
+        })));
+        let stats = simulator.run();
+        assert_eq!(stats.num_failed, 1);
+        assert_eq!(simulator.get_network().get_num_failures(&cid("Bob", "Dave"), ErrorType::NoSlots), 2);
+    }
+
+    #[test]
+    fn simulator_stats_by_traffic_class() {
+        // Bob - Dave has a single slot, which a hold invoice keeps busy
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = Network::new(None);
+        add_channel(&mut network, "Alice", "Bob", 0.0);
+        testutils::add_channel(&mut network, "Bob", "Dave", ChannelInDirection::new(Some(1), None, None, None, None));
+        let mut schedule = Schedule::new(Timestamp(100));
+        let hold_time = HoldTimeDistribution::new(Duration(60), Duration(3600));
+        let event = Event::new_hold_invoice(node("Alice"), node("Dave"), Satoshi(100), &hold_time, &mut rng);
+        schedule.put_event(Timestamp(0), event, None);
+        schedule.put_event(Timestamp(10), example_event(Satoshi(100)), None);
+        let mut simulator = Simulator::new(network, schedule, None, 0);
+        simulator.run();
+        let hold_invoice_stats = simulator.get_class_stats(&TrafficClass::HoldInvoice);
+        assert_eq!((hold_invoice_stats.num_events, hold_invoice_stats.num_reached_receiver), (1, 1));
+        let regular_stats = simulator.get_class_stats(&TrafficClass::Regular);
+        assert_eq!((regular_stats.num_events, regular_stats.num_failed), (1, 1));
+        assert_eq!(simulator.get_stats().num_events, 2);
+    }
+
+    #[test]
+    fn simulator_hold_fees_by_traffic_class() {
+        // Bob - Dave sets a hold fee: regular payments resolve within the grace period, hold invoices don't
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut network = Network::new(None);
+        add_channel(&mut network, "Alice", "Bob", 0.0);
+        let mut ch_in_dir = ChannelInDirection::new(None, None, None, None, None);
+        ch_in_dir.hold_fee = Some(HoldFee::new(1000, Duration(10), Satoshi(1_000_000)));
+        testutils::add_channel(&mut network, "Bob", "Dave", ch_in_dir);
+        let mut schedule = Schedule::new(Timestamp(100));
+        let hold_time = HoldTimeDistribution::new(Duration(60), Duration(3600));
+        let event = Event::new_hold_invoice(node("Alice"), node("Dave"), Satoshi(100_000), &hold_time, &mut rng);
+        schedule.put_event(Timestamp(0), event, None);
+        schedule.put_event(Timestamp(10), example_event(Satoshi(100_000)), None);
+        let mut simulator = Simulator::new(network, schedule, None, 0);
+        simulator.run();
+        let network = simulator.get_network();
+        assert!(network.get_class_fees(&TrafficClass::HoldInvoice).hold > 0);
+        assert_eq!(network.get_class_fees(&TrafficClass::Regular).hold, 0);
+        // hold invoice senders pay the hold fees of all hops
+        assert_eq!(
+            network.get_class_fees(&TrafficClass::HoldInvoice).hold,
+            -network.get_revenue(&node("Alice")).hold,
+        );
+    }
+
+    #[test]
+    fn simulator_stale_fee_retry() {
+        // Bob charges ten times the upfront fee towards Dave once one of his two slots is busy
+        let mut network = Network::new(None);
+        add_channel(&mut network, "Alice", "Bob", 0.0);
+        let congestion_fee = CongestionFee {
+            base: Fee::new(Satoshi(2), FeeRate(0.02)),
+            curve: PricingCurve::Step { threshold: 0.5, multiplier: 10.0 },
+        };
+        let ch_in_dir = ChannelInDirection::new(Some(2), Some(Box::new(congestion_fee)), None, None, None);
+        testutils::add_channel(&mut network, "Bob", "Dave", ch_in_dir);
+        network.set_gossip_delay(Duration(10));
+        let mut schedule = Schedule::new(Timestamp(20));
+        let long_event = Event::new(node("Alice"), node("Dave"), Satoshi(1000), Duration(100), PaymentResult::SUCCESS, None);
+        schedule.put_event(Timestamp(0), long_event, None);
+        schedule.put_event(Timestamp(1), example_event(Satoshi(1000)), None);
+        let mut simulator = Simulator::new(network, schedule, Some(sender_params(3, false)), 0);
+        let stats = simulator.run().clone();
+        // the failure carries the channel update, so the retry pays the higher fee long before gossip arrives
+        assert_eq!(stats.num_attempts, 3);
+        assert_eq!(stats.num_reached_receiver, 2);
+        assert_eq!(simulator.get_network().get_num_stale_fee_failures(), 1);
+    }
+
+    #[test]
+    fn simulator_no_retries() {
+        let mut simulator = Simulator::new(example_network(), example_schedule(None), None, 0);
+        let stats = simulator.run().clone();
+        assert_eq!(stats, SimulationStats {
+            num_events: 1,
+            num_attempts: 1,
+            num_parts_sent: 1,
+            num_reached_receiver: 0,
+            num_failed: 1,
+        });
+        let network = simulator.get_network();
+        assert_eq!(network.get_num_failures(&cid("Bob", "Dave"), ErrorType::FailedDeliberately), 1);
+    }
+
+    #[test]
+    fn simulator_retry_excludes_erring_channel() {
+        let mut simulator = Simulator::new(
+            example_network(), example_schedule(None), Some(sender_params(3, false)), 0);
+        let stats = simulator.run().clone();
+        assert_eq!(stats, SimulationStats {
+            num_events: 1,
+            num_attempts: 2,
+            num_parts_sent: 2,
+            num_reached_receiver: 1,
+            num_failed: 0,
+        });
+        // the second attempt doesn't touch the erring channel
+        let network = simulator.get_network();
+        assert_eq!(network.get_num_failures(&cid("Bob", "Dave"), ErrorType::FailedDeliberately), 1);
+        // only the successful route counts the payment as through
+        assert_eq!(simulator.get_num_parts_through(&node("Alice"), &node("Bob")), 0);
+        assert_eq!(simulator.get_num_parts_through(&node("Alice"), &node("Charlie")), 1);
+        assert_eq!(simulator.get_num_parts_through(&node("Eve"), &node("Dave")), 1);
+    }
+
+    #[test]
+    fn simulator_retry_over_parallel_channel() {
+        // Bob and Dave have a second, honest channel: the retry goes over it along the same route
+        let mut network = Network::new(None);
+        add_channel(&mut network, "Alice", "Bob", 0.0);
+        add_channel(&mut network, "Bob", "Dave", 1.0);
+        testutils::add_bidirectional_channel(
+            &mut network, "Dave", "Bob", || ChannelInDirection::new(None, None, None, None, None));
+        let mut simulator = Simulator::new(network, example_schedule(None), Some(sender_params(3, false)), 0);
+        let stats = simulator.run();
+        assert_eq!((stats.num_attempts, stats.num_reached_receiver), (2, 1));
+        let network = simulator.get_network();
+        assert_eq!(network.get_num_failures(&cid("Bob", "Dave"), ErrorType::FailedDeliberately), 1);
+        assert_eq!(simulator.get_num_parts_through(&node("Bob"), &node("Dave")), 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn simulator_zero_retry_delay() {
+        let mut params = sender_params(3, false);
+        params.retry_delay = Duration(0);
+        let _simulator = Simulator::new(example_network(), example_schedule(None), Some(params), 0);
+    }
+
+    #[test]
+    fn simulator_retry_time_budget() {
+        let mut params = sender_params(3, false);
+        params.retry_delay = Duration(6);
+        let mut simulator = Simulator::new(
+            example_network(), example_schedule(None), Some(params), 0);
+        let stats = simulator.run();
+        assert_eq!(stats.num_attempts, 1);
+        assert_eq!(stats.num_failed, 1);
+    }
+
+    #[test]
+    fn simulator_reroute_around_must_route_via_nodes() {
+        let must_route_via_nodes = Some(vec![node("Bob")]);
+        // without rerouting, there is no route via Bob after the first failure
+        let mut simulator = Simulator::new(
+            example_network(), example_schedule(must_route_via_nodes.clone()), Some(sender_params(3, false)), 0);
+        let stats = simulator.run();
+        assert_eq!(stats.num_attempts, 1);
+        assert_eq!(stats.num_failed, 1);
+        // with rerouting, the second attempt avoids Bob
+        let mut simulator = Simulator::new(
+            example_network(), example_schedule(must_route_via_nodes), Some(sender_params(3, true)), 0);
+        let stats = simulator.run();
+        assert_eq!(stats.num_attempts, 2);
+        assert_eq!(stats.num_reached_receiver, 1);
+    }
+
+    #[test]
+    fn simulator_scorer_routes_around_failed_channel() {
+        let schedule = || {
+            let mut schedule = Schedule::new(Timestamp(10));
+            // events must differ, or the schedule would store only one of them
+            for (time, amount) in [(0, 100), (1, 101)] {
+                let event = Event::new(
+                    node("Alice"), node("Dave"), Satoshi(amount), Duration(5), PaymentResult::SUCCESS, None);
+                schedule.put_event(Timestamp(time), event, None);
+            }
+            schedule
+        };
+        // without scoring, every payment takes the shortest route and fails
+        let mut simulator = Simulator::new(example_network(), schedule(), None, 0);
+        assert_eq!(simulator.run().num_failed, 2);
+        // with scoring, the second payment takes the longer route
+        let mut params = sender_params(1, false);
+        // the failure is remembered for long enough to matter
+        params.scorer_params = Some(ScorerParams {
+            half_life: Duration(10_000),
+            penalty_multiplier: 10.0,
+        });
+        let mut simulator = Simulator::new(example_network(), schedule(), Some(params), 0);
+        let stats = simulator.run();
+        assert_eq!(stats.num_failed, 1);
+        assert_eq!(stats.num_reached_receiver, 1);
+    }
+
+    #[test]
+    fn simulator_scorer_ignores_non_liquidity_failures() {
+        // Bob - Dave reports being disabled, which says nothing about its liquidity
+        let mut network = Network::new(None);
+        add_channel(&mut network, "Alice", "Bob", 0.0);
+        let ch_in_dir = ChannelInDirection::new(None, None, None, Some(1.0), Some(ErrorType::ChannelDisabled));
+        testutils::add_channel(&mut network, "Bob", "Dave", ch_in_dir);
+        let scorer_params = ScorerParams {
+            half_life: Duration(10_000),
+            penalty_multiplier: 10.0,
+        };
+        let mut params = sender_params(1, false);
+        params.scorer_params = Some(scorer_params.clone());
+        let mut simulator = Simulator::new(network, example_schedule(None), Some(params), 0);
+        assert_eq!(simulator.run().num_failed, 1);
+        let cid = cid("Bob", "Dave");
+        let direction = Direction::new(&node("Bob"), &node("Dave"));
+        let capacity = simulator.get_network().get_channel(&cid).unwrap().get_capacity();
+        let success_probability = |scorer: &Scorer| {
+            scorer.success_probability(&cid, &direction, capacity, &Satoshi(100), &Timestamp(0))
+        };
+        assert_eq!(
+            success_probability(&simulator.scorers[&node("Alice")]),
+            success_probability(&Scorer::new(scorer_params)),
+        );
+    }
+
+    #[test]
+    fn simulator_mpp_parts_take_diverse_routes() {
+        // two honest routes from Alice to Dave
+        let mut network = Network::new(None);
+        add_channel(&mut network, "Alice", "Bob", 0.0);
+        add_channel(&mut network, "Bob", "Dave", 0.0);
+        add_channel(&mut network, "Alice", "Charlie", 0.0);
+        add_channel(&mut network, "Charlie", "Dave", 0.0);
+        let mut params = sender_params(1, false);
+        params.split_strategy = SplitStrategy::EqualParts(2);
+        let mut simulator = Simulator::new(network, example_schedule(None), Some(params), 0);
+        let num_slots_busy = |simulator: &Simulator, from, to| simulator.get_network()
+            .get_channel_in_direction(&node(from), &node(to)).unwrap().get_num_slots_busy();
+        simulator.handle_event(Timestamp(0), example_event(Satoshi(1000)));
+        assert_eq!(simulator.get_stats().num_parts_sent, 2);
+        assert_eq!(simulator.get_stats().num_reached_receiver, 1);
+        assert_eq!(num_slots_busy(&simulator, "Bob", "Dave"), 1);
+        assert_eq!(num_slots_busy(&simulator, "Charlie", "Dave"), 1);
+    }
+
+    fn mpp_simulator(hop_delay: Option<HopDelay>) -> Simulator {
+        // the route via Bob works, the one via Charlie does not
+        let mut network = Network::new(None);
+        add_channel(&mut network, "Alice", "Bob", 0.0);
+        add_channel(&mut network, "Bob", "Dave", 0.0);
+        add_channel(&mut network, "Alice", "Charlie", 0.0);
+        add_channel(&mut network, "Charlie", "Dave", 1.0);
+        if let Some(hop_delay) = hop_delay {
+            network.set_hop_delay(hop_delay);
+        }
+        let mut params = sender_params(1, false);
+        params.split_strategy = SplitStrategy::EqualParts(2);
+        params.mpp_timeout = Duration(3);
+        Simulator::new(network, example_schedule(None), Some(params), 0)
+    }
+
+    fn uneven_mpp_simulator(mpp_timeout: Duration) -> Simulator {
+        // one part goes via Bob, the other takes a hop longer via Charlie and Eve
+        let mut network = Network::new(None);
+        add_channel(&mut network, "Alice", "Bob", 0.0);
+        add_channel(&mut network, "Bob", "Dave", 0.0);
+        add_channel(&mut network, "Alice", "Charlie", 0.0);
+        add_channel(&mut network, "Charlie", "Eve", 0.0);
+        add_channel(&mut network, "Eve", "Dave", 0.0);
+        network.set_hop_delay(HopDelay::new(Duration(1), Duration(1)));
+        let mut params = sender_params(1, false);
+        params.split_strategy = SplitStrategy::EqualParts(2);
+        params.mpp_timeout = mpp_timeout;
+        Simulator::new(network, example_schedule(None), Some(params), 0)
+    }
+
+    fn get_lock_ends(simulator: &Simulator, directions: &[(&str, &str)]) -> Vec<Timestamp> {
+        directions.iter()
+            .map(|(from, to)| simulator.get_network().get_channel_in_direction(&node(from), &node(to)).unwrap()
+                .get_earliest_htlc_resolution_time().clone())
+            .collect()
+    }
+
+    #[test]
+    fn simulator_mpp_parts_resolve_together() {
+        // the parts reach Dave at 2 and 3: Dave settles both at 3 + 5, then each hop back takes a time unit
+        let mut simulator = uneven_mpp_simulator(Duration(3));
+        simulator.handle_event(Timestamp(0), example_event(Satoshi(1000)));
+        assert_eq!(simulator.get_stats().num_reached_receiver, 1);
+        assert_eq!(get_lock_ends(&simulator, &[("Alice", "Bob"), ("Bob", "Dave")]), vec![Timestamp(10), Timestamp(9)]);
+        assert_eq!(
+            get_lock_ends(&simulator, &[("Alice", "Charlie"), ("Charlie", "Eve"), ("Eve", "Dave")]),
+            vec![Timestamp(11), Timestamp(10), Timestamp(9)],
+        );
+    }
+
+    #[test]
+    fn simulator_mpp_last_part_after_timeout() {
+        // Dave gives up on the payment at 2, before the second part arrives at 3, and fails that one on arrival
+        let mut simulator = uneven_mpp_simulator(Duration(0));
+        simulator.handle_event(Timestamp(0), example_event(Satoshi(1000)));
+        assert_eq!(simulator.get_stats().num_reached_receiver, 0);
+        assert_eq!(simulator.get_stats().num_failed, 1);
+        assert_eq!(get_lock_ends(&simulator, &[("Alice", "Bob"), ("Bob", "Dave")]), vec![Timestamp(4), Timestamp(3)]);
+        assert_eq!(
+            get_lock_ends(&simulator, &[("Alice", "Charlie"), ("Charlie", "Eve"), ("Eve", "Dave")]),
+            vec![Timestamp(6), Timestamp(5), Timestamp(4)],
+        );
+    }
+
+    #[test]
+    fn simulator_mpp_fails_all_parts() {
+        let mut simulator = mpp_simulator(None);
+        simulator.handle_event(Timestamp(0), example_event(Satoshi(1000)));
+        assert_eq!(simulator.get_stats().num_parts_sent, 2);
+        assert_eq!(simulator.get_stats().num_failed, 1);
+        // the part that went via Bob has reached Dave, and is failed back right away
+        for (from, to) in [("Alice", "Bob"), ("Bob", "Dave")] {
+            let ch_in_dir = simulator.get_network().get_channel_in_direction(&node(from), &node(to)).unwrap();
+            assert_eq!(ch_in_dir.get_earliest_htlc_resolution_time(), &Timestamp(0));
+            assert_eq!(ch_in_dir.get_num_unendorsed_htlcs_in_flight(&Timestamp(0)), 0);
+        }
+    }
+
+    #[test]
+    fn simulator_mpp_part_on_its_way_waits_for_timeout() {
+        // the part via Bob reaches Dave at time 2, after the other part has failed:
+        // Dave holds it for the MPP timeout
+        let mut simulator = mpp_simulator(Some(HopDelay::new(Duration(1), Duration(1))));
+        simulator.handle_event(Timestamp(0), example_event(Satoshi(1000)));
+        assert_eq!(simulator.get_stats().num_failed, 1);
+        let ch_in_dir = simulator.get_network().get_channel_in_direction(&node("Bob"), &node("Dave")).unwrap();
+        assert_eq!(ch_in_dir.get_num_unendorsed_htlcs_in_flight(&Timestamp(5)), 1);
+        assert_eq!(ch_in_dir.get_num_unendorsed_htlcs_in_flight(&Timestamp(6)), 0);
+    }
+
+    #[test]
+    fn simulator_mpp_failed_part_staggered() {
+        // Dave fails the part via Bob at 5, and the failure takes a time unit per hop back to Alice
+        let mut simulator = mpp_simulator(Some(HopDelay::new(Duration(1), Duration(1))));
+        simulator.handle_event(Timestamp(0), example_event(Satoshi(1000)));
+        assert_eq!(get_lock_ends(&simulator, &[("Alice", "Bob"), ("Bob", "Dave")]), vec![Timestamp(7), Timestamp(6)]);
+    }
+}
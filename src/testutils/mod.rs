@@ -0,0 +1,67 @@
+use crate::channel::Channel as Channel;
+use crate::channel::channelindirection::ChannelInDirection as ChannelInDirection;
+use crate::channel::direction::Direction as Direction;
+use crate::common::{
+    channelid::ChannelId,
+    fee::{Fee, FeeRate},
+    feepolicy::FeePolicy,
+    nodeid::NodeId,
+    satoshi::Satoshi,
+};
+use crate::network::Network as Network;
+
+// Fixtures shared by the tests of all modules.
+// Channels are named after the nodes they connect, e.g., AliceBob.
+
+pub const CAPACITY: Satoshi = Satoshi(1_000_000);
+
+pub fn node(name: &str) -> NodeId {
+    NodeId(String::from(name))
+}
+
+pub fn nodes(names: &[&str]) -> Vec<NodeId> {
+    names.iter().map(|name| node(name)).collect()
+}
+
+pub fn cid(from: &str, to: &str) -> ChannelId {
+    ChannelId(format!("{}{}", from, to))
+}
+
+pub fn flat_fee(base_fee: u64) -> Option<Box<dyn FeePolicy>> {
+    Some(Box::new(Fee::new(Satoshi(base_fee), FeeRate(0.0))))
+}
+
+pub fn add_channel(network: &mut Network, from: &str, to: &str, ch_in_dir: ChannelInDirection) {
+    // the channel is only enabled from the first node to the second
+    let mut ch = Channel::new(cid(from, to), CAPACITY);
+    ch.set_channel_in_direction(Direction::new(&node(from), &node(to)), Some(ch_in_dir));
+    network.add_channel(node(from), node(to), ch);
+}
+
+pub fn add_channel_with_balance(
+    network: &mut Network,
+    from: &str,
+    to: &str,
+    balance: Satoshi,
+    ch_in_dir: ChannelInDirection,
+) {
+    // the first node's side holds the given balance, the second node's side the rest
+    let direction = Direction::new(&node(from), &node(to));
+    let mut ch = Channel::new(cid(from, to), CAPACITY);
+    ch.set_balance(direction, balance);
+    ch.set_channel_in_direction(direction, Some(ch_in_dir));
+    network.add_channel(node(from), node(to), ch);
+}
+
+pub fn add_bidirectional_channel(
+    network: &mut Network,
+    node_a: &str,
+    node_b: &str,
+    new_ch_in_dir: impl Fn() -> ChannelInDirection,
+) {
+    let mut ch = Channel::new(cid(node_a, node_b), CAPACITY);
+    for (from, to) in [(node_a, node_b), (node_b, node_a)] {
+        ch.set_channel_in_direction(Direction::new(&node(from), &node(to)), Some(new_ch_in_dir()));
+    }
+    network.add_channel(node(node_a), node(node_b), ch);
+}